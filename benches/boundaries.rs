@@ -0,0 +1,150 @@
+//! Grid queries of `Boundaries` against a linear scan over the same obstacles:
+//! `cargo bench --bench boundaries`.
+
+// its unit tests come along and are not run here
+#[allow(dead_code, unused_imports)]
+#[path = "../src/boundaries.rs"]
+mod boundaries;
+#[allow(dead_code)]
+#[path = "../src/geometry.rs"]
+mod geometry;
+
+use bevy::{
+    math::{Vec2, Vec3, Vec3Swizzles},
+    sprite::collide_aabb::collide,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use boundaries::{ray_aabb, Boundaries};
+use geometry::PLAYER_SIZE;
+
+// about a big level, 64x64 tiles of 32 pixels
+const EXTENT: f32 = 2048.;
+const OBSTACLES: usize = 400;
+const QUERIES: usize = 100;
+
+fn random_layout(rng: &mut impl Rng) -> Vec<(Vec3, Vec2)> {
+    (0..OBSTACLES)
+        .map(|_| {
+            let pos = random_point(rng).extend(0.);
+            let size = Vec2::new(rng.gen_range(16. ..160.), rng.gen_range(16. ..160.));
+            (pos, size)
+        })
+        .collect()
+}
+
+fn random_point(rng: &mut impl Rng) -> Vec2 {
+    Vec2::new(rng.gen_range(0. ..EXTENT), rng.gen_range(0. ..EXTENT))
+}
+
+fn random_dir(rng: &mut impl Rng) -> Vec2 {
+    let angle = rng.gen_range(0. ..std::f32::consts::TAU);
+    Vec2::new(angle.cos(), angle.sin())
+}
+
+fn overlaps(center: Vec2, half_size: Vec2, (pos, size): &(Vec3, Vec2)) -> bool {
+    let offset = (center - pos.xy()).abs();
+    let reach = half_size + *size / 2.;
+    offset.x <= reach.x && offset.y <= reach.y
+}
+
+fn queries(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(11);
+    let obstacles = random_layout(&mut rng);
+    let bounds = Boundaries::new(obstacles.clone());
+    let points: Vec<_> = (0..QUERIES).map(|_| random_point(&mut rng)).collect();
+    let dirs: Vec<_> = (0..QUERIES).map(|_| random_dir(&mut rng)).collect();
+    let player_size = Vec2::splat(PLAYER_SIZE);
+    let half_size = Vec2::splat(100.);
+    let radius = 250.;
+    let max_dist = 500.;
+
+    let mut group = c.benchmark_group("collide");
+    group.bench_function("grid", |b| {
+        b.iter(|| {
+            for point in points.iter() {
+                black_box(bounds.collide(point.extend(0.)));
+            }
+        })
+    });
+    group.bench_function("linear", |b| {
+        b.iter(|| {
+            for point in points.iter() {
+                black_box(
+                    obstacles.iter().find_map(|(pos, size)| {
+                        collide(*pos, *size, point.extend(0.), player_size)
+                    }),
+                );
+            }
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("query_aabb");
+    group.bench_function("grid", |b| {
+        b.iter(|| {
+            for point in points.iter() {
+                black_box(bounds.query_aabb(*point, half_size).count());
+            }
+        })
+    });
+    group.bench_function("linear", |b| {
+        b.iter(|| {
+            for point in points.iter() {
+                black_box(
+                    obstacles
+                        .iter()
+                        .filter(|o| overlaps(*point, half_size, o))
+                        .count(),
+                );
+            }
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("query_radius");
+    group.bench_function("grid", |b| {
+        b.iter(|| {
+            for point in points.iter() {
+                black_box(bounds.query_radius(*point, radius).count());
+            }
+        })
+    });
+    group.bench_function("linear", |b| {
+        b.iter(|| {
+            for point in points.iter() {
+                let in_radius = obstacles.iter().filter(|(pos, size)| {
+                    let closest = point.clamp(pos.xy() - *size / 2., pos.xy() + *size / 2.);
+                    (closest - *point).length() <= radius
+                });
+                black_box(in_radius.count());
+            }
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("raycast");
+    group.bench_function("grid", |b| {
+        b.iter(|| {
+            for (origin, dir) in points.iter().zip(dirs.iter()) {
+                black_box(bounds.raycast(*origin, *dir, max_dist));
+            }
+        })
+    });
+    group.bench_function("linear", |b| {
+        b.iter(|| {
+            for (origin, dir) in points.iter().zip(dirs.iter()) {
+                let dist = obstacles
+                    .iter()
+                    .filter_map(|(pos, size)| ray_aabb(*origin, *dir, pos.xy(), *size / 2.))
+                    .fold(max_dist, f32::min);
+                black_box(dist);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
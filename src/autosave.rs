@@ -0,0 +1,224 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
+};
+
+use bevy::{log, prelude::*, window::WindowCloseRequested};
+
+use crate::{
+    error_screen::ErrorMessage,
+    items::PlayerItems,
+    profiles::Profiles,
+    progress::Progress,
+    save::{self, SaveError, SaveFile},
+};
+
+/// Seconds without changes to the saved resources before they are written.
+const AUTOSAVE_DELAY: f32 = 2.;
+
+#[derive(Debug, Clone, Copy)]
+pub enum SaveStatus {
+    Never,
+    Pending,
+    Saving,
+    Saved(Instant),
+    Failed,
+    /// The save is from a newer version of the game.
+    Disabled,
+}
+
+#[derive(Debug, Default)]
+struct Writer {
+    /// Background writes started before this was bumped are stale and skipped.
+    generation: u64,
+    result: Option<Result<(), SaveError>>,
+}
+
+/// Writes `PlayerItems` and `Progress` on a background thread once they stop changing.
+pub struct Autosave {
+    /// Seconds left until the write, `None` if nothing changed.
+    countdown: Option<f32>,
+    in_flight: bool,
+    generation: u64,
+    /// Held by whoever is writing, so two writes never race on the same files.
+    writer: Arc<Mutex<Writer>>,
+    last_saved: Option<Instant>,
+    failed: bool,
+    /// The last write was refused, the save is from a newer version of the game.
+    disabled: bool,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Autosave {
+            countdown: None,
+            in_flight: false,
+            generation: 0,
+            writer: Arc::new(Mutex::new(Writer::default())),
+            last_saved: None,
+            failed: false,
+            disabled: false,
+        }
+    }
+}
+
+impl Autosave {
+    /// Skips the delay, the write still happens in the background.
+    pub fn save_soon(&mut self) {
+        self.countdown = Some(0.);
+    }
+
+    /// Blocks until any background write is done and writes right away.
+    pub fn save_now(&mut self, path: &Path, save_file: &SaveFile) -> Result<(), SaveError> {
+        let mut writer = self.writer.lock().expect("autosave thread panicked");
+        // a background write that has not started yet would overwrite this one with older items
+        self.generation += 1;
+        writer.generation = self.generation;
+        writer.result = None;
+        let result = save::write(path, save_file);
+        self.in_flight = false;
+        self.countdown = None;
+        self.set_result(&result);
+        result
+    }
+
+    /// Waits for a background write to finish and drops the pending one, for when the save
+    /// is about to be deleted.
+    pub fn cancel(&mut self) {
+        let mut writer = self.writer.lock().expect("autosave thread panicked");
+        // a write that has not started yet would bring the deleted files back
+        self.generation += 1;
+        writer.generation = self.generation;
+        writer.result = None;
+        self.in_flight = false;
+        self.countdown = None;
+    }
+
+    pub fn status(&self) -> SaveStatus {
+        if self.in_flight {
+            SaveStatus::Saving
+        } else if self.countdown.is_some() {
+            SaveStatus::Pending
+        } else if self.disabled {
+            SaveStatus::Disabled
+        } else if self.failed {
+            SaveStatus::Failed
+        } else if let Some(time) = self.last_saved {
+            SaveStatus::Saved(time)
+        } else {
+            SaveStatus::Never
+        }
+    }
+
+    fn set_result(&mut self, result: &Result<(), SaveError>) {
+        self.disabled = matches!(result, Err(SaveError::UnknownVersion(_)));
+        self.failed = result.is_err() && !self.disabled;
+        if result.is_ok() {
+            self.last_saved = Some(Instant::now());
+        }
+    }
+
+    fn spawn_write(&mut self, path: PathBuf, save_file: SaveFile) {
+        self.in_flight = true;
+        let generation = self.generation;
+        let writer = self.writer.clone();
+        thread::spawn(move || {
+            let mut writer = writer.lock().expect("main thread panicked while saving");
+            if writer.generation == generation {
+                writer.result = Some(save::write(&path, &save_file));
+            }
+        });
+    }
+}
+
+fn track_changes(
+    items: Res<PlayerItems>,
+    progress: Res<Progress>,
+    mut autosave: ResMut<Autosave>,
+    mut is_loaded: Local<bool>,
+) {
+    // the first change is the save being loaded
+    if !*is_loaded {
+        *is_loaded = true;
+        return;
+    }
+    if items.is_changed() || progress.is_changed() {
+        autosave.countdown = Some(AUTOSAVE_DELAY);
+    }
+}
+
+fn write_when_idle(
+    time: Res<Time>,
+    items: Res<PlayerItems>,
+    progress: Res<Progress>,
+    profiles: Res<Profiles>,
+    mut autosave: ResMut<Autosave>,
+) {
+    let countdown = match autosave.countdown {
+        Some(countdown) => countdown - time.delta_seconds(),
+        None => return,
+    };
+    if countdown > 0. || autosave.in_flight {
+        autosave.countdown = Some(countdown.max(0.));
+        return;
+    }
+    autosave.countdown = None;
+    log::debug!("autosaving");
+    autosave.spawn_write(profiles.save_path(), SaveFile::new(&items, &progress));
+}
+
+fn collect_result(mut autosave: ResMut<Autosave>, mut error: ResMut<Option<ErrorMessage>>) {
+    if !autosave.in_flight {
+        return;
+    }
+    let result = match autosave.writer.try_lock() {
+        Ok(mut writer) => writer.result.take(),
+        Err(_) => return,
+    };
+    // the thread has not started yet if there is no result
+    if let Some(result) = result {
+        autosave.in_flight = false;
+        autosave.set_result(&result);
+        match result {
+            Ok(()) => {}
+            // the player was told when the save was loaded
+            Err(SaveError::UnknownVersion(_)) => log::warn!("not saving over a newer save"),
+            Err(e) => {
+                log::error!("cant autosave: {}", e);
+                *error = Some(ErrorMessage {
+                    title: "Could not save".to_string(),
+                    details: e.to_string(),
+                });
+            }
+        }
+    }
+}
+
+fn save_on_close(
+    mut events: EventReader<WindowCloseRequested>,
+    items: Res<PlayerItems>,
+    progress: Res<Progress>,
+    profiles: Res<Profiles>,
+    mut autosave: ResMut<Autosave>,
+) {
+    if events.iter().next().is_some() {
+        log::info!("saving before exit");
+        let save_file = SaveFile::new(&items, &progress);
+        if let Err(e) = autosave.save_now(&profiles.save_path(), &save_file) {
+            log::error!("cant save before exit: {}", e);
+        }
+    }
+}
+
+pub struct AutosavePlugin;
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Autosave>()
+            .add_system(track_changes.system().label("track_changes"))
+            .add_system(write_when_idle.system().after("track_changes"))
+            .add_system(collect_result.system())
+            .add_system(save_on_close.system());
+    }
+}
@@ -0,0 +1,265 @@
+//! Checks levels without starting the game: `cargo run --bin lint_level [map.tmx...]`.
+//! Lints every map in `assets` when no paths are given, exits with 1 if any has errors.
+
+#[allow(dead_code)]
+#[path = "../boundaries.rs"]
+mod boundaries;
+#[path = "../geometry.rs"]
+mod geometry;
+#[allow(dead_code)]
+#[path = "../map_validation.rs"]
+mod map_validation;
+#[allow(dead_code)]
+#[path = "../pathfinding.rs"]
+mod pathfinding;
+
+use std::{
+    f32::consts::TAU,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use bevy::math::Vec2;
+
+use boundaries::Boundaries;
+use geometry::{cone_points, is_in_triangle, PLAYER_SIZE};
+use map_validation::{validate, CameraLayout, GuardLayout, LevelLayout, GUARD_FOV, GUARD_RADIUS};
+use pathfinding::NavGrid;
+
+// sweeping cameras are checked at this many headings
+const SWEEP_SAMPLES: u32 = 64;
+// guards are checked every this many pixels of their route
+const ROUTE_STEP: f32 = 16.;
+
+enum Severity {
+    Error,
+    Warning,
+}
+
+struct Report {
+    path: PathBuf,
+    errors: u32,
+}
+
+impl Report {
+    fn print(&mut self, severity: Severity, message: String) {
+        let severity = match severity {
+            Severity::Error => {
+                self.errors += 1;
+                "error"
+            }
+            Severity::Warning => "warning",
+        };
+        println!("{}: {}: {}", self.path.display(), severity, message);
+    }
+}
+
+/// Headings the camera can turn to, in radians from the authored angles.
+fn headings(camera: &CameraLayout) -> Vec<f32> {
+    let range = match &camera.sweep {
+        None => return vec![0.],
+        Some(sweep) => sweep.range.unwrap_or(TAU),
+    };
+    (0..=SWEEP_SAMPLES)
+        .map(|i| -range / 2. + range * i as f32 / SWEEP_SAMPLES as f32)
+        .collect()
+}
+
+fn is_watched(bounds: &Boundaries, camera: &CameraLayout, pos: Vec2) -> bool {
+    let relative = pos - camera.pos;
+    if relative.length() > camera.radius {
+        return false;
+    }
+    let is_in_cone = headings(camera).into_iter().any(|heading| {
+        let cone = cone_points(
+            camera.start_angle + heading,
+            camera.end_angle + heading,
+            camera.radius,
+        );
+        is_in_triangle(relative, cone)
+    });
+    is_in_cone && bounds.is_visible(camera.pos, pos)
+}
+
+/// Spots along the route with the heading there, guards look where they walk.
+fn guard_views(guard: &GuardLayout) -> Vec<(Vec2, f32)> {
+    if guard.waypoints.len() < 2 {
+        return guard.waypoints.iter().map(|pos| (*pos, 0.)).collect();
+    }
+    let mut legs: Vec<_> = guard.waypoints.windows(2).map(|w| (w[0], w[1])).collect();
+    if guard.looped {
+        legs.push((
+            guard.waypoints[guard.waypoints.len() - 1],
+            guard.waypoints[0],
+        ));
+    } else {
+        // walked back the other way
+        let back: Vec<_> = legs.iter().map(|(from, to)| (*to, *from)).collect();
+        legs.extend(back);
+    }
+    legs.into_iter()
+        .flat_map(|(from, to)| {
+            let dir = to - from;
+            let heading = dir.y.atan2(dir.x);
+            let steps = (dir.length() / ROUTE_STEP).ceil().max(1.) as u32;
+            (0..=steps).map(move |i| (from.lerp(to, i as f32 / steps as f32), heading))
+        })
+        .collect()
+}
+
+fn is_seen_by_guard(bounds: &Boundaries, guard: &GuardLayout, pos: Vec2) -> bool {
+    let radius = guard.radius.unwrap_or(GUARD_RADIUS);
+    let fov = guard.fov.unwrap_or_else(|| GUARD_FOV.to_radians());
+    guard_views(guard).into_iter().any(|(spot, heading)| {
+        let relative = pos - spot;
+        let cone = cone_points(heading - fov / 2., heading + fov / 2., radius);
+        relative.length() <= radius
+            && is_in_triangle(relative, cone)
+            && bounds.is_visible(spot, pos)
+    })
+}
+
+/// Any walkable cell around `pos` connected to the spawn, pickups reach a bit past the player.
+fn is_reachable(grid: &NavGrid, spawn: (usize, usize), pos: Vec2, half_size: Vec2) -> bool {
+    let (min, max) = match (grid.cell_at(pos - half_size), grid.cell_at(pos + half_size)) {
+        (Some(min), Some(max)) => (min, max),
+        _ => return false,
+    };
+    (min.0..=max.0)
+        .flat_map(|x| (min.1..=max.1).map(move |y| (x, y)))
+        .any(|cell| grid.is_walkable(cell) && grid.find_cell_path(spawn, cell).is_some())
+}
+
+fn lint_layout(report: &mut Report, layout: &LevelLayout) {
+    let player_half = Vec2::splat(PLAYER_SIZE / 2.);
+    let bounds = Boundaries::new(layout.obstacles.clone());
+    let grid = NavGrid::from_boundaries(
+        layout.width as usize,
+        layout.height as usize,
+        layout.tile_size,
+        &bounds,
+    );
+    if bounds.collide(layout.spawn.extend(0.)).is_some() {
+        report.print(
+            Severity::Error,
+            format!("spawn point {} is inside an obstacle", layout.spawn),
+        );
+    }
+    for (index, camera) in layout.cameras.iter().enumerate() {
+        if is_watched(&bounds, camera, layout.spawn) {
+            report.print(
+                Severity::Error,
+                format!("camera {} at {} covers the spawn point", index, camera.pos),
+            );
+        }
+    }
+    for (index, guard) in layout.guards.iter().enumerate() {
+        for waypoint in guard.waypoints.iter() {
+            if bounds.is_blocked(*waypoint) {
+                report.print(
+                    Severity::Warning,
+                    format!(
+                        "waypoint {} of guard {} is inside an obstacle",
+                        waypoint, index
+                    ),
+                );
+            }
+        }
+        if is_seen_by_guard(&bounds, guard, layout.spawn) {
+            report.print(
+                Severity::Error,
+                format!("guard {} sees the spawn point", index),
+            );
+        }
+    }
+    let spawn = grid
+        .cell_at(layout.spawn)
+        .filter(|cell| grid.is_walkable(*cell));
+    for treasure in layout.treasures.iter() {
+        if bounds.is_blocked(*treasure) {
+            report.print(
+                Severity::Error,
+                format!("treasure {} is inside an obstacle", treasure),
+            );
+        } else if !spawn.map_or(false, |spawn| {
+            is_reachable(&grid, spawn, *treasure, player_half)
+        }) {
+            report.print(
+                Severity::Error,
+                format!("treasure {} cant be reached from the spawn", treasure),
+            );
+        }
+        for (index, camera) in layout.cameras.iter().enumerate() {
+            if is_watched(&bounds, camera, *treasure) {
+                report.print(
+                    Severity::Warning,
+                    format!("treasure {} is watched by camera {}", treasure, index),
+                );
+            }
+        }
+        for (index, guard) in layout.guards.iter().enumerate() {
+            if is_seen_by_guard(&bounds, guard, *treasure) {
+                report.print(
+                    Severity::Warning,
+                    format!("treasure {} is watched by guard {}", treasure, index),
+                );
+            }
+        }
+    }
+    for (exit, size) in layout.exits.iter() {
+        if !spawn.map_or(false, |spawn| is_reachable(&grid, spawn, *exit, *size / 2.)) {
+            report.print(
+                Severity::Error,
+                format!("exit {} cant be reached from the spawn", exit),
+            );
+        }
+    }
+}
+
+fn lint(path: &Path) -> u32 {
+    let mut report = Report {
+        path: path.to_path_buf(),
+        errors: 0,
+    };
+    let map = match tiled::parse_file(path) {
+        Ok(map) => map,
+        Err(e) => {
+            report.print(Severity::Error, format!("cant parse: {}", e));
+            return report.errors;
+        }
+    };
+    match validate(&map) {
+        Ok(layout) => lint_layout(&mut report, &layout),
+        Err(problems) => {
+            for problem in problems {
+                report.print(Severity::Error, problem.to_string());
+            }
+        }
+    }
+    report.errors
+}
+
+fn asset_maps() -> Vec<PathBuf> {
+    let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+    let mut maps: Vec<_> = fs::read_dir(&assets)
+        .unwrap_or_else(|e| panic!("cant read {}: {}", assets.display(), e))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "tmx"))
+        .collect();
+    maps.sort();
+    maps
+}
+
+fn main() {
+    let mut paths: Vec<PathBuf> = std::env::args().skip(1).map(PathBuf::from).collect();
+    if paths.is_empty() {
+        paths = asset_maps();
+    }
+    let errors: u32 = paths.iter().map(|path| lint(path)).sum();
+    if errors > 0 {
+        println!("{} errors", errors);
+        process::exit(1);
+    }
+    println!("{} maps ok", paths.len());
+}
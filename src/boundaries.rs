@@ -0,0 +1,430 @@
+use std::cmp::Ordering;
+
+use bevy::{
+    math::{Vec2, Vec3, Vec3Swizzles},
+    sprite::collide_aabb::{collide, Collision},
+    utils::HashMap,
+};
+use itertools::Itertools;
+
+use crate::geometry::PLAYER_SIZE;
+
+// a few tiles per cell, most obstacles end up in one or two cells
+const GRID_CELL_SIZE: f32 = 128.;
+
+/// Obstacles of the level as (center, size), bucketed into a uniform grid for queries.
+#[derive(Default)]
+pub struct Boundaries {
+    obstacles: Vec<(Vec3, Vec2)>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl Boundaries {
+    pub fn new(obstacles: Vec<(Vec3, Vec2)>) -> Self {
+        let mut cells: HashMap<_, Vec<_>> = HashMap::default();
+        for (index, (pos, size)) in obstacles.iter().enumerate() {
+            let half_size = *size / 2.;
+            for cell in cells_in(pos.xy() - half_size, pos.xy() + half_size) {
+                cells.entry(cell).or_default().push(index);
+            }
+        }
+        Boundaries { obstacles, cells }
+    }
+
+    pub fn collide(&self, player_pos: Vec3) -> Option<Collision> {
+        let player_size = Vec2::splat(PLAYER_SIZE);
+        self.query_aabb(player_pos.xy(), player_size / 2.)
+            .find_map(|(pos, size)| collide(*pos, *size, player_pos, player_size))
+    }
+
+    pub fn is_blocked(&self, point: Vec2) -> bool {
+        self.candidates(point, point).any(|(pos, size)| {
+            let half_size = *size / 2.;
+            let min = pos.xy() - half_size;
+            let max = pos.xy() + half_size;
+            point.x >= min.x && point.x <= max.x && point.y >= min.y && point.y <= max.y
+        })
+    }
+
+    /// Obstacles overlapping the box, each at most once.
+    pub fn query_aabb(&self, center: Vec2, half_size: Vec2) -> impl Iterator<Item = &(Vec3, Vec2)> {
+        let min = center - half_size;
+        let max = center + half_size;
+        self.candidates(min, max).filter(move |(pos, size)| {
+            let (other_min, other_max) = (pos.xy() - *size / 2., pos.xy() + *size / 2.);
+            min.x <= other_max.x
+                && max.x >= other_min.x
+                && min.y <= other_max.y
+                && max.y >= other_min.y
+        })
+    }
+
+    /// Obstacles with any part closer than `radius` to `center`.
+    pub fn query_radius(&self, center: Vec2, radius: f32) -> impl Iterator<Item = &(Vec3, Vec2)> {
+        self.query_aabb(center, Vec2::splat(radius))
+            .filter(move |(pos, size)| {
+                let half_size = *size / 2.;
+                let closest = center.clamp(pos.xy() - half_size, pos.xy() + half_size);
+                (closest - center).length_squared() <= radius * radius
+            })
+    }
+
+    /// Distance from `origin` along normalized `dir` to the first obstacle, capped by `max_dist`.
+    /// Obstacles containing `origin` are ignored, cameras tend to be mounted right on the walls.
+    pub fn raycast(&self, origin: Vec2, dir: Vec2, max_dist: f32) -> f32 {
+        // walks the grid cells the ray passes through, Amanatides & Woo
+        let mut cell = cell_at(origin);
+        let step = |d: f32| if d > 0. { 1 } else { -1 };
+        let first_boundary = |o: f32, d: f32, c: i32| {
+            if d.abs() < f32::EPSILON {
+                f32::INFINITY
+            } else {
+                let edge = if d > 0. { c + 1 } else { c };
+                (edge as f32 * GRID_CELL_SIZE - o) / d
+            }
+        };
+        let delta = |d: f32| {
+            if d.abs() < f32::EPSILON {
+                f32::INFINITY
+            } else {
+                GRID_CELL_SIZE / d.abs()
+            }
+        };
+        let mut t_max_x = first_boundary(origin.x, dir.x, cell.0);
+        let mut t_max_y = first_boundary(origin.y, dir.y, cell.1);
+        let (t_delta_x, t_delta_y) = (delta(dir.x), delta(dir.y));
+        let mut closest = max_dist;
+        loop {
+            for index in self.cells.get(&cell).into_iter().flatten() {
+                let (pos, size) = &self.obstacles[*index];
+                if let Some(t) = ray_aabb(origin, dir, pos.xy(), *size / 2.) {
+                    closest = closest.min(t);
+                }
+            }
+            let t_exit = t_max_x.min(t_max_y);
+            // anything in the next cells is further than what was found already
+            if closest <= t_exit || t_exit > max_dist {
+                return closest;
+            }
+            if t_max_x < t_max_y {
+                cell.0 += step(dir.x);
+                t_max_x += t_delta_x;
+            } else {
+                cell.1 += step(dir.y);
+                t_max_y += t_delta_y;
+            }
+        }
+    }
+
+    pub fn is_visible(&self, from: Vec2, to: Vec2) -> bool {
+        let diff = to - from;
+        let dist = diff.length();
+        if dist <= f32::EPSILON {
+            return true;
+        }
+        self.raycast(from, diff / dist, dist) >= dist
+    }
+
+    /// Earliest obstacle hit by a box moving by `motion`, as the fraction of `motion` done
+    /// before the contact and the normal of the hit side.
+    /// Obstacles the box already overlaps are ignored, so it can always get out of them.
+    pub fn sweep(&self, center: Vec2, half_size: Vec2, motion: Vec2) -> Option<(f32, Vec2)> {
+        let end = center + motion;
+        let min = center.min(end) - half_size;
+        let max = center.max(end) + half_size;
+        self.candidates(min, max)
+            .filter_map(|(pos, size)| sweep_aabb(center, motion, pos.xy(), *size / 2. + half_size))
+            .min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+    }
+
+    // obstacles sharing a cell with the box, without repeats
+    fn candidates(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = &(Vec3, Vec2)> {
+        let mut indices = cells_in(min, max)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(move |index| &self.obstacles[index])
+    }
+}
+
+fn cell_at(point: Vec2) -> (i32, i32) {
+    (
+        (point.x / GRID_CELL_SIZE).floor() as i32,
+        (point.y / GRID_CELL_SIZE).floor() as i32,
+    )
+}
+
+fn cells_in(min: Vec2, max: Vec2) -> impl Iterator<Item = (i32, i32)> {
+    let (min_x, min_y) = cell_at(min);
+    let (max_x, max_y) = cell_at(max);
+    (min_y..=max_y).flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)))
+}
+
+// slab method against the obstacle grown by the moving box, touching is not a hit
+fn sweep_aabb(origin: Vec2, motion: Vec2, center: Vec2, half_size: Vec2) -> Option<(f32, Vec2)> {
+    let min = center - half_size;
+    let max = center + half_size;
+    let mut t_min = f32::NEG_INFINITY;
+    let mut t_max = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+    let axes = [
+        (origin.x, motion.x, min.x, max.x, Vec2::X),
+        (origin.y, motion.y, min.y, max.y, Vec2::Y),
+    ];
+    for (o, d, lo, hi, axis) in axes {
+        if d.abs() < f32::EPSILON {
+            if o <= lo || o >= hi {
+                return None;
+            }
+        } else {
+            let t1 = (lo - o) / d;
+            let t2 = (hi - o) / d;
+            let near = t1.min(t2);
+            if near > t_min {
+                t_min = near;
+                normal = if d > 0. { -axis } else { axis };
+            }
+            t_max = t_max.min(t1.max(t2));
+        }
+    }
+    if t_max <= t_min || !(0. ..=1.).contains(&t_min) {
+        None
+    } else {
+        Some((t_min, normal))
+    }
+}
+
+// slab method, returns distance to the entry point
+pub(crate) fn ray_aabb(origin: Vec2, dir: Vec2, center: Vec2, half_size: Vec2) -> Option<f32> {
+    let min = center - half_size;
+    let max = center + half_size;
+    let mut t_min = f32::NEG_INFINITY;
+    let mut t_max = f32::INFINITY;
+    let axes = [
+        (origin.x, dir.x, min.x, max.x),
+        (origin.y, dir.y, min.y, max.y),
+    ];
+    for (o, d, lo, hi) in axes {
+        if d.abs() < f32::EPSILON {
+            if o < lo || o > hi {
+                return None;
+            }
+        } else {
+            let t1 = (lo - o) / d;
+            let t2 = (hi - o) / d;
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+        }
+    }
+    if t_max < t_min || t_min < 0. {
+        None
+    } else {
+        Some(t_min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn random_layout(rng: &mut impl Rng, count: usize, extent: f32) -> Vec<(Vec3, Vec2)> {
+        (0..count)
+            .map(|_| {
+                let pos = random_point(rng, extent).extend(0.);
+                let size = Vec2::new(rng.gen_range(4. ..300.), rng.gen_range(4. ..300.));
+                (pos, size)
+            })
+            .collect()
+    }
+
+    // negative coordinates too, cells below zero are floored
+    fn random_point(rng: &mut impl Rng, extent: f32) -> Vec2 {
+        Vec2::new(
+            rng.gen_range(-extent / 4. ..extent),
+            rng.gen_range(-extent / 4. ..extent),
+        )
+    }
+
+    fn overlaps(min: Vec2, max: Vec2, (pos, size): &(Vec3, Vec2)) -> bool {
+        let (other_min, other_max) = (pos.xy() - *size / 2., pos.xy() + *size / 2.);
+        min.x <= other_max.x && max.x >= other_min.x && min.y <= other_max.y && max.y >= other_min.y
+    }
+
+    fn sorted<'a>(obstacles: impl Iterator<Item = &'a (Vec3, Vec2)>) -> Vec<(Vec3, Vec2)> {
+        let mut obstacles = obstacles.copied().collect_vec();
+        obstacles.sort_by(|(a, _), (b, _)| {
+            (a.x, a.y)
+                .partial_cmp(&(b.x, b.y))
+                .unwrap_or(Ordering::Equal)
+        });
+        obstacles
+    }
+
+    fn linear_collide(obstacles: &[(Vec3, Vec2)], player_pos: Vec3) -> Option<Collision> {
+        let player_size = Vec2::splat(PLAYER_SIZE);
+        obstacles
+            .iter()
+            .find_map(|(pos, size)| collide(*pos, *size, player_pos, player_size))
+    }
+
+    #[test]
+    fn queries_match_a_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..20 {
+            let obstacles = random_layout(&mut rng, 80, 1000.);
+            let bounds = Boundaries::new(obstacles.clone());
+            for _ in 0..100 {
+                let point = random_point(&mut rng, 1000.);
+                assert_eq!(
+                    bounds.is_blocked(point),
+                    obstacles.iter().any(|o| overlaps(point, point, o)),
+                    "point {}",
+                    point
+                );
+
+                let half_size = Vec2::new(rng.gen_range(0. ..200.), rng.gen_range(0. ..200.));
+                assert_eq!(
+                    sorted(bounds.query_aabb(point, half_size)),
+                    sorted(obstacles.iter().filter(|o| overlaps(
+                        point - half_size,
+                        point + half_size,
+                        o
+                    ))),
+                    "box at {} of {}",
+                    point,
+                    half_size
+                );
+
+                let radius = rng.gen_range(0. ..300.);
+                let in_radius = obstacles.iter().filter(|(pos, size)| {
+                    let closest = point.clamp(pos.xy() - *size / 2., pos.xy() + *size / 2.);
+                    (closest - point).length() <= radius
+                });
+                assert_eq!(
+                    sorted(bounds.query_radius(point, radius)),
+                    sorted(in_radius),
+                    "radius {} at {}",
+                    radius,
+                    point
+                );
+
+                // grid candidates are in the same order as the list, so the same obstacle is found
+                assert_eq!(
+                    format!("{:?}", bounds.collide(point.extend(0.))),
+                    format!("{:?}", linear_collide(&obstacles, point.extend(0.))),
+                    "player at {}",
+                    point
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn raycast_matches_a_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(12);
+        for _ in 0..20 {
+            let obstacles = random_layout(&mut rng, 80, 1000.);
+            let bounds = Boundaries::new(obstacles.clone());
+            for _ in 0..100 {
+                let origin = random_point(&mut rng, 1000.);
+                let angle = rng.gen_range(0. ..std::f32::consts::TAU);
+                let dir = Vec2::new(angle.cos(), angle.sin());
+                let max_dist = rng.gen_range(0. ..1500.);
+                let expected = obstacles
+                    .iter()
+                    .filter_map(|(pos, size)| ray_aabb(origin, dir, pos.xy(), *size / 2.))
+                    .fold(max_dist, f32::min);
+                let dist = bounds.raycast(origin, dir, max_dist);
+                assert!(
+                    (dist - expected).abs() < 1e-3,
+                    "ray from {} along {}: {} instead of {}",
+                    origin,
+                    dir,
+                    dist,
+                    expected
+                );
+            }
+        }
+    }
+
+    fn wall() -> Boundaries {
+        // x from 50 to 70
+        Boundaries::new(vec![(Vec3::new(60., 0., 0.), Vec2::new(20., 200.))])
+    }
+
+    #[test]
+    fn sweep_stops_at_the_contact() {
+        let half_size = Vec2::splat(16.);
+        let (t, normal) = wall()
+            .sweep(Vec2::ZERO, half_size, Vec2::new(100., 0.))
+            .unwrap();
+        // the right side of the box reaches x = 50 at x = 34
+        assert!((t - 0.34).abs() < 1e-6, "{}", t);
+        assert_eq!(normal, Vec2::new(-1., 0.));
+
+        let (t, normal) = wall()
+            .sweep(Vec2::new(120., 30.), half_size, Vec2::new(-100., -50.))
+            .unwrap();
+        assert!((t - 0.34).abs() < 1e-6, "{}", t);
+        assert_eq!(normal, Vec2::new(1., 0.));
+    }
+
+    #[test]
+    fn sweep_hits_the_side_entered_first() {
+        let half_size = Vec2::splat(16.);
+        // above the wall, coming down onto its top
+        let (t, normal) = wall()
+            .sweep(Vec2::new(60., 150.), half_size, Vec2::new(10., -100.))
+            .unwrap();
+        assert!((t - 0.34).abs() < 1e-6, "{}", t);
+        assert_eq!(normal, Vec2::new(0., 1.));
+    }
+
+    #[test]
+    fn sweep_misses() {
+        let half_size = Vec2::splat(16.);
+        let bounds = wall();
+        // stops short
+        assert_eq!(
+            bounds.sweep(Vec2::ZERO, half_size, Vec2::new(30., 0.)),
+            None
+        );
+        // away from it
+        assert_eq!(
+            bounds.sweep(Vec2::ZERO, half_size, Vec2::new(-100., 0.)),
+            None
+        );
+        // touching the side and moving along it
+        assert_eq!(
+            bounds.sweep(Vec2::new(34., 0.), half_size, Vec2::new(0., 100.)),
+            None
+        );
+        // passing over the top
+        assert_eq!(
+            bounds.sweep(Vec2::new(0., 150.), half_size, Vec2::new(150., 0.)),
+            None
+        );
+        // already inside, it has to be able to get out
+        assert_eq!(
+            bounds.sweep(Vec2::new(60., 0.), half_size, Vec2::new(100., 0.)),
+            None
+        );
+    }
+
+    #[test]
+    fn sweep_finds_the_nearest_obstacle() {
+        let bounds = Boundaries::new(vec![
+            (Vec3::new(300., 0., 0.), Vec2::new(20., 20.)),
+            (Vec3::new(60., 0., 0.), Vec2::new(20., 20.)),
+        ]);
+        let (t, _) = bounds
+            .sweep(Vec2::ZERO, Vec2::splat(16.), Vec2::new(400., 0.))
+            .unwrap();
+        assert!((t - 34. / 400.).abs() < 1e-6, "{}", t);
+    }
+}
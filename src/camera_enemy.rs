@@ -1,11 +1,20 @@
+use std::f32::consts::TAU;
+
 use bevy::{
+    log,
     math::{Mat2, Vec2, Vec3Swizzles},
     prelude::*,
 };
 
 use crate::{
+    boundaries::Boundaries,
+    effects::{ActiveEffects, Decoy},
+    emp::EmpPulse,
+    geometry::{cone_points, is_in_triangle},
+    movement::Velocity,
+    objective::LevelObjective,
     perlin::{NoiseColorComponent, PerlinBundle, PerlinPipelineHandle},
-    player::{Dashing, Player},
+    player::{Dashing, LevelMarker, Player},
     smoke_bomb::SmokeBomb,
     GameState,
 };
@@ -18,68 +27,240 @@ pub struct CameraSpawn {
     pub start_angle: f32,
     pub end_angle: f32,
     pub radius: f32,
+    pub sweep: Option<CameraSweep>,
+}
+
+/// How a camera turns around its mount, angles are in radians.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraSweep {
+    /// radians per second
+    pub speed: f32,
+    /// whole swing centered on the authored cone, `None` makes the camera spin around
+    pub range: Option<f32>,
+    /// seconds to wait at both ends of the swing
+    pub pause: f32,
 }
 
 const NOISE_RESOLUTION: f32 = 2000.;
 const NOISE_OCTAVE: f32 = 0.15;
-const TRANSPARENCY_BASES: [f32; 3] = [0.8, 0.2, 0.2];
-const CAMERA_INDICES: [u32; 3] = [0, 1, 2];
+const TRANSPARENCY_BASES: [f32; 2] = [0.8, 0.2];
+const CONE_DIVISIONS: u32 = 32;
 
 #[derive(Debug)]
-struct Camera {
+pub(crate) struct Camera {
     // in radians
     start_angle: f32,
     // in radians
     end_angle: f32,
     radius: f32,
-    // TODO: I don't really need any of the above fields
     points: [Vec2; 3],
+    sweep: Option<CameraSweep>,
+    // in radians, offset from the authored angles
+    heading: f32,
+    direction: f32,
+    pause_left: f32,
+}
+
+impl Camera {
+    pub(crate) fn new(
+        start_angle: f32,
+        end_angle: f32,
+        radius: f32,
+        sweep: Option<CameraSweep>,
+    ) -> Self {
+        Camera {
+            start_angle,
+            end_angle,
+            radius,
+            points: cone_points(start_angle, end_angle, radius),
+            sweep,
+            heading: 0.,
+            direction: 1.,
+            pause_left: 0.,
+        }
+    }
+
+    /// Turns the whole cone, `heading` is added to the authored angles.
+    pub(crate) fn look_at(&mut self, heading: f32) {
+        self.heading = heading;
+        self.update_points();
+    }
+
+    fn update_points(&mut self) {
+        let (start_angle, end_angle) = self.angles();
+        self.points = cone_points(start_angle, end_angle, self.radius);
+    }
+
+    fn angles(&self) -> (f32, f32) {
+        (
+            self.start_angle + self.heading,
+            self.end_angle + self.heading,
+        )
+    }
+
+    fn turn(&mut self, delta: f32) {
+        let sweep = if let Some(x) = self.sweep {
+            x
+        } else {
+            return;
+        };
+        if self.pause_left > 0. {
+            self.pause_left -= delta;
+            return;
+        }
+        self.heading += sweep.speed * self.direction * delta;
+        match sweep.range {
+            Some(range) => {
+                let half = range / 2.;
+                if self.heading.abs() >= half {
+                    self.heading = self.heading.clamp(-half, half);
+                    self.direction = -self.direction;
+                    self.pause_left = sweep.pause;
+                }
+            }
+            None => self.heading = self.heading.rem_euclid(TAU),
+        }
+        self.update_points();
+    }
 }
 
+/// How close the player is to being caught, from 0 to 1. Full meter is a game over.
+#[derive(Debug, Default)]
+pub struct Suspicion(pub f32);
+
+/// per second, player standing still at the very edge of a cone
+const SUSPICION_RATE: f32 = 0.8;
+const CLOSE_SUSPICION_MULTI: f32 = 2.;
+const MOVING_SUSPICION_MULTI: f32 = 1.5;
+/// per second, while no camera sees the player
+const SUSPICION_DECAY: f32 = 0.25;
+
+/// Camera hit by an EMP, can't see anything until the timer runs out.
+pub struct DisabledCamera(Timer);
+
 fn spawn_camera(
     mut commands: Commands,
     spawns: Res<Vec<CameraSpawn>>,
     pp_handle: Res<PerlinPipelineHandle>,
     mut meshes: ResMut<Assets<Mesh>>,
+    boundaries: Res<Boundaries>,
 ) {
-    // x is used for transparency going further from start
     for spawn in spawns.iter() {
-        let mut mesh = Mesh::new(bevy::render::pipeline::PrimitiveTopology::TriangleList);
-        let mut v_pos = vec![[0., 0.]];
-        let angles = [spawn.start_angle, spawn.end_angle];
-        let radius = spawn.radius;
-        let origin = Vec2::new(radius, 0.);
-        for angle in angles {
-            let rotation_mat = Mat2::from_angle(angle);
-            let res = rotation_mat * origin;
-            v_pos.push(res.into());
+        let camera = Camera::new(
+            spawn.start_angle,
+            spawn.end_angle,
+            spawn.radius,
+            spawn.sweep,
+        );
+        spawn_vision(
+            &mut commands,
+            camera,
+            Vec3::new(spawn.x, spawn.y, 0.2),
+            &pp_handle,
+            &mut meshes,
+            &boundaries,
+        );
+    }
+}
+
+/// Spawns anything that watches for the player, cameras and guards alike.
+pub(crate) fn spawn_vision(
+    commands: &mut Commands,
+    camera: Camera,
+    position: Vec3,
+    pp_handle: &PerlinPipelineHandle,
+    meshes: &mut Assets<Mesh>,
+    boundaries: &Boundaries,
+) -> Entity {
+    let (start_angle, end_angle) = camera.angles();
+    let mesh = cone_mesh(
+        position.xy(),
+        start_angle,
+        end_angle,
+        camera.radius,
+        boundaries,
+    );
+    commands
+        .spawn_bundle(MeshBundle {
+            mesh: meshes.add(mesh),
+            transform: Transform::from_translation(position),
+            ..Default::default()
+        })
+        .insert(camera)
+        .insert(LevelMarker)
+        .insert_bundle(PerlinBundle::new(
+            pp_handle,
+            NOISE_RESOLUTION,
+            NOISE_OCTAVE,
+            base_color(),
+        ))
+        .id()
+}
+
+fn sweep_cameras(time: Res<Time>, cameras: Query<&mut Camera, Without<DisabledCamera>>) {
+    let delta = time.delta_seconds();
+    cameras.for_each_mut(|mut cam| {
+        if cam.sweep.is_some() {
+            cam.turn(delta);
+        }
+    });
+}
+
+#[allow(clippy::type_complexity)]
+fn update_cones(
+    cameras: Query<(&Camera, &Transform, &Handle<Mesh>), Or<(Changed<Camera>, Changed<Transform>)>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    boundaries: Res<Boundaries>,
+) {
+    cameras.for_each(|(cam, tr, mesh_handle)| {
+        let (start_angle, end_angle) = cam.angles();
+        if let Some(mesh) = meshes.get_mut(mesh_handle) {
+            *mesh = cone_mesh(
+                tr.translation.xy(),
+                start_angle,
+                end_angle,
+                cam.radius,
+                &boundaries,
+            );
+        }
+    });
+}
+
+/// Vision cone as a fan of rays, each one cut short by the first obstacle on its way.
+fn cone_mesh(
+    origin: Vec2,
+    start_angle: f32,
+    end_angle: f32,
+    radius: f32,
+    boundaries: &Boundaries,
+) -> Mesh {
+    // x is used for transparency going further from start
+    let [near, far] = TRANSPARENCY_BASES;
+    let mut v_pos = vec![[0., 0.]];
+    let mut uv = vec![near];
+    let mut indices = vec![];
+    let step = (end_angle - start_angle) / CONE_DIVISIONS as f32;
+    // nothing in reach, no need to cast the rays
+    let is_clear = boundaries.query_radius(origin, radius).next().is_none();
+    for i in 0..=CONE_DIVISIONS {
+        let dir = Mat2::from_angle(start_angle + step * i as f32) * Vec2::X;
+        let len = if is_clear {
+            radius
+        } else {
+            boundaries.raycast(origin, dir, radius)
+        };
+        v_pos.push((dir * len).into());
+        // fade stays relative to the full radius, clipped rays just end earlier
+        uv.push(near + (far - near) * len / radius);
+        if i > 0 {
+            indices.extend_from_slice(&[0, i, i + 1]);
         }
-        let points: [Vec2; 3] = [v_pos[0].into(), v_pos[1].into(), v_pos[2].into()];
-        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, v_pos);
-        mesh.set_indices(Some(bevy::render::mesh::Indices::U32(
-            CAMERA_INDICES.to_vec(),
-        )));
-        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, TRANSPARENCY_BASES.to_vec());
-
-        commands
-            .spawn_bundle(MeshBundle {
-                mesh: meshes.add(mesh),
-                transform: Transform::from_xyz(spawn.x, spawn.y, 0.2),
-                ..Default::default()
-            })
-            .insert(Camera {
-                start_angle: spawn.start_angle,
-                end_angle: spawn.end_angle,
-                radius: spawn.radius,
-                points,
-            })
-            .insert_bundle(PerlinBundle::new(
-                &pp_handle,
-                NOISE_RESOLUTION,
-                NOISE_OCTAVE,
-                base_color(),
-            ));
     }
+    let mut mesh = Mesh::new(bevy::render::pipeline::PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, v_pos);
+    mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices)));
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uv);
+    mesh
 }
 
 fn base_color() -> Vec3 {
@@ -90,75 +271,181 @@ fn detected_color() -> Vec3 {
     Vec3::new(0.9, 0.1, 0.)
 }
 
-// https://stackoverflow.com/questions/2049582/how-to-determine-if-a-point-is-in-a-2d-triangle
-fn is_in_triangle(s: Vec2, triangle: [Vec2; 3]) -> bool {
-    let [a, b, c] = triangle;
-    let as_x = s.x - a.x;
-    let as_y = s.y - a.y;
-
-    let s_ab = (b.x - a.x) * as_y - (b.y - a.y) * as_x > 0.;
-    let s_ac = (c.x - a.x) * as_y - (c.y - a.y) * as_x > 0.;
-
-    if s_ac == s_ab {
-        return false;
-    }
+fn disabled_color() -> Vec3 {
+    Vec3::new(0.1, 0.2, 0.4)
+}
 
-    let last = (c.x - b.x) * (s.y - b.y) - (c.y - b.y) * (s.x - b.x) > 0.;
+fn emp_cameras(
+    mut commands: Commands,
+    pulses: Query<(&EmpPulse, &Transform), Added<EmpPulse>>,
+    cameras: Query<(Entity, &Transform), With<Camera>>,
+) {
+    pulses.for_each(|(pulse, pulse_tr)| {
+        let pulse_pos = pulse_tr.translation.xy();
+        cameras.for_each(|(entity, tr)| {
+            let dist = tr.translation.xy() - pulse_pos;
+            if dist.length_squared() < pulse.radius * pulse.radius {
+                log::debug!("camera disabled by emp");
+                commands
+                    .entity(entity)
+                    .insert(DisabledCamera(Timer::from_seconds(pulse.duration, false)));
+            }
+        });
+    });
+}
 
-    last == s_ab
+fn tick_disabled_cameras(
+    mut commands: Commands,
+    time: Res<Time>,
+    query: Query<(Entity, &mut DisabledCamera)>,
+) {
+    query.for_each_mut(|(entity, mut disabled)| {
+        if disabled.0.tick(time.delta()).finished() {
+            log::debug!("camera back online");
+            commands.entity(entity).remove::<DisabledCamera>();
+        }
+    });
 }
 
 fn detect_player(
-    cameras: Query<(&Camera, &Transform, &mut NoiseColorComponent)>,
+    cameras: Query<(
+        &Camera,
+        &Transform,
+        &mut NoiseColorComponent,
+        Option<&DisabledCamera>,
+    )>,
     smoke_bombs: Query<(&SmokeBomb, &Transform)>,
-    player: Query<&Transform, (With<Player>, Without<Dashing>)>,
+    player: Query<(&Transform, &Velocity), (With<Player>, Without<Dashing>)>,
+    decoys: Query<&Transform, With<Decoy>>,
+    boundaries: Res<Boundaries>,
+    effects: Res<ActiveEffects>,
+    time: Res<Time>,
+    mut suspicion: ResMut<Suspicion>,
+    mut objective: ResMut<LevelObjective>,
     mut state: ResMut<State<GameState>>,
 ) {
-    let player_tr = if let Ok(x) = player.single() {
-        x.translation.xy()
+    // dashing player can't be seen
+    let seen_rate = if let Ok((player_tr, velocity)) = player.single() {
+        let player_tr = player_tr.translation.xy();
+        let mut is_smoked = false;
+        smoke_bombs.for_each(|(bomb, tr)| {
+            let dist = player_tr - tr.translation.xy();
+            if dist.length_squared() < bomb.radius * bomb.radius {
+                is_smoked = true;
+                // TODO: break early?
+            }
+        });
+        let is_moving = velocity.0 != Vec2::ZERO;
+        let mut seen_rate: f32 = 0.;
+        cameras.for_each_mut(|(cam, tr, _, disabled)| {
+            if is_smoked || disabled.is_some() {
+                return;
+            }
+            let cam_tr = tr.translation.xy();
+            let sees = |pos: Vec2| {
+                is_in_triangle(pos - cam_tr, cam.points) && boundaries.is_visible(cam_tr, pos)
+            };
+            let relative_tr = player_tr - cam_tr;
+            if !sees(player_tr) {
+                return;
+            }
+            let closest_decoy = decoys
+                .iter()
+                .map(|decoy| decoy.translation.xy())
+                .filter(|pos| sees(*pos))
+                .map(|pos| (pos - cam_tr).length())
+                .reduce(f32::min);
+            if !is_distracted(relative_tr.length(), closest_decoy) {
+                let rate = suspicion_rate(relative_tr.length() / cam.radius, is_moving)
+                    * effects.suspicion_multi();
+                seen_rate = seen_rate.max(rate);
+            }
+        });
+        seen_rate
     } else {
-        return;
+        0.
     };
-    let mut is_smoked = false;
-    smoke_bombs.for_each(|(bomb, tr)| {
-        let dist = player_tr - tr.translation.xy();
-        if dist.length_squared() < bomb.radius * bomb.radius {
-            is_smoked = true;
-            // TODO: break early?
+
+    let delta = time.delta_seconds();
+    if seen_rate > 0. {
+        if suspicion.0 <= 0. {
+            objective.detections += 1;
         }
-    });
-    let base_color = base_color();
-    let detected_color = detected_color();
-    let mut is_detected = false;
-    cameras.for_each_mut(|(cam, tr, mut color)| {
-        let tr = tr.translation.xy();
-        let player_tr = player_tr - tr;
-        if !is_smoked && is_in_triangle(player_tr, cam.points) {
-            color.value = detected_color;
-            is_detected = true;
+        suspicion.0 += seen_rate * delta;
+    } else {
+        suspicion.0 -= SUSPICION_DECAY * delta;
+    }
+    suspicion.0 = suspicion.0.clamp(0., 1.);
+
+    let color = base_color().lerp(detected_color(), suspicion.0);
+    let disabled_color = disabled_color();
+    cameras.for_each_mut(|(_, _, mut noise_color, disabled)| {
+        noise_color.value = if disabled.is_some() {
+            disabled_color
         } else {
-            color.value = base_color;
-        }
+            color
+        };
     });
-    if is_detected {
+    if suspicion.0 >= 1. {
         state
             .push(GameState::GameOver)
             .expect("cant move to gameover");
     }
 }
 
+/// A camera watching a decoy closer than the player is busy with it and misses the player.
+fn is_distracted(player_distance: f32, closest_decoy: Option<f32>) -> bool {
+    closest_decoy.map_or(false, |decoy| decoy < player_distance)
+}
+
+/// `distance` is relative to the camera radius, cameras notice closer and moving players faster.
+fn suspicion_rate(distance: f32, is_moving: bool) -> f32 {
+    let closeness = 1. - distance.clamp(0., 1.);
+    let rate = SUSPICION_RATE * (1. + CLOSE_SUSPICION_MULTI * closeness);
+    if is_moving {
+        rate * MOVING_SUSPICION_MULTI
+    } else {
+        rate
+    }
+}
+
+fn reset_suspicion(mut suspicion: ResMut<Suspicion>) {
+    suspicion.0 = 0.;
+}
+
 pub struct EnemyCameraPlugin;
 impl Plugin for EnemyCameraPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<Vec<CameraSpawn>>()
+            .init_resource::<Suspicion>()
             .add_system_set(
-                SystemSet::on_enter(GameState::Level).with_system(spawn_camera.system()),
+                SystemSet::on_enter(GameState::Level)
+                    .with_system(spawn_camera.system())
+                    .with_system(reset_suspicion.system()),
             )
             .add_system_set(
-                SystemSet::on_update(GameState::Level).with_system(detect_player.system()),
+                SystemSet::on_update(GameState::Level)
+                    .with_system(emp_cameras.system())
+                    .with_system(tick_disabled_cameras.system())
+                    .with_system(sweep_cameras.system().label("sweep_cameras"))
+                    .with_system(update_cones.system().after("sweep_cameras"))
+                    .with_system(detect_player.system().after("sweep_cameras")),
                 // SystemSet::new()
                 //     .with_run_criteria(FixedTimestep::steps_per_second(1.))
                 //     .with_system(detect_player.system()),
             );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_closer_decoys_distract() {
+        assert!(!is_distracted(100., None));
+        assert!(is_distracted(100., Some(50.)));
+        // the player walking right up to the camera gets noticed anyway
+        assert!(!is_distracted(50., Some(100.)));
+    }
+}
@@ -11,7 +11,8 @@ use bevy::{
 };
 
 use crate::{
-    player::{Casting, Player, PLAYER_SIZE},
+    geometry::PLAYER_SIZE,
+    player::{Casting, Player},
     GameState,
 };
 
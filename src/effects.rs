@@ -0,0 +1,100 @@
+use std::fmt::Display;
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{items::PlayerItems, player::Player, GameState};
+
+pub const DECOY_DURATION: f32 = 3.;
+
+/// Changes how a skill or the enemies work, only found on uniques.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Effect {
+    /// Smoke bombs stay centered on the player.
+    FollowingSmoke,
+    /// Dashing leaves a decoy behind, cameras that see it ignore the player behind it.
+    DashDecoy,
+    /// Cameras grow suspicious this many times faster.
+    Conspicuous(f32),
+}
+
+impl Display for Effect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Effect::FollowingSmoke => write!(f, "Smoke Bomb follows you"),
+            Effect::DashDecoy => write!(f, "Dash leaves a decoy"),
+            Effect::Conspicuous(multi) => {
+                write!(
+                    f,
+                    "Cameras notice you {}% faster",
+                    ((multi - 1.) * 100.) as i32
+                )
+            }
+        }
+    }
+}
+
+/// Effects of the equipped items, set with `PlayerStatsMods` when a level starts.
+#[derive(Debug, Clone, Default)]
+pub struct ActiveEffects {
+    effects: Vec<Effect>,
+}
+
+impl ActiveEffects {
+    pub fn from_items(items: &PlayerItems) -> Self {
+        ActiveEffects {
+            effects: items
+                .all_equipped_items()
+                .flat_map(|item| item.effects.iter().copied())
+                .collect(),
+        }
+    }
+
+    pub fn smoke_follows_player(&self) -> bool {
+        self.effects.contains(&Effect::FollowingSmoke)
+    }
+
+    pub fn dash_leaves_decoy(&self) -> bool {
+        self.effects.contains(&Effect::DashDecoy)
+    }
+
+    /// Multiplies the rate cameras grow suspicious at, stacks with every conspicuous item.
+    pub fn suspicion_multi(&self) -> f32 {
+        self.effects
+            .iter()
+            .map(|effect| match effect {
+                Effect::Conspicuous(multi) => *multi,
+                _ => 1.,
+            })
+            .product()
+    }
+}
+
+/// Keeps the entity under the player.
+pub struct FollowPlayer;
+
+/// Looks like the player to the cameras.
+pub struct Decoy;
+
+fn follow_player(
+    player: Query<&Transform, With<Player>>,
+    followers: Query<&mut Transform, (With<FollowPlayer>, Without<Player>)>,
+) {
+    let player = match player.single() {
+        Ok(tr) => tr.translation.xy(),
+        Err(_) => return,
+    };
+    followers.for_each_mut(|mut tr| {
+        tr.translation.x = player.x;
+        tr.translation.y = player.y;
+    });
+}
+
+pub struct EffectsPlugin;
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ActiveEffects>().add_system_set(
+            SystemSet::on_update(GameState::Level).with_system(follow_player.system()),
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::{log, prelude::*};
+
+use crate::{
+    button::{register_my_button, ClickedButtonEvent, MyButton, MyButtonBundle},
+    cleanup::cleanup_system,
+    main_menu_ui::{change_camera_scale_from_resize, light_text_color},
+    GameState, RobotoFont, HEIGHT, WIDTH,
+};
+
+/// What went wrong, shown on `GameState::ErrorScreen`.
+/// Set it and move to the error screen, or leave it set in the main menu and it will show up.
+#[derive(Debug, Clone)]
+pub struct ErrorMessage {
+    pub title: String,
+    pub details: String,
+}
+
+struct ErrorScreenMarker;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ClickedOk;
+
+struct BackgroundMaterial(Handle<ColorMaterial>);
+
+fn show_pending_error(message: Res<Option<ErrorMessage>>, mut state: ResMut<State<GameState>>) {
+    if message.is_some() {
+        state
+            .set(GameState::ErrorScreen)
+            .expect("cant move to error screen");
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut message: ResMut<Option<ErrorMessage>>,
+    font: Res<RobotoFont>,
+    background: Res<BackgroundMaterial>,
+) {
+    let message = message.take().unwrap_or_else(|| ErrorMessage {
+        title: "Something went wrong".to_string(),
+        details: String::new(),
+    });
+    log::debug!(?message, "showing an error");
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite::new(Vec2::new(WIDTH, HEIGHT)),
+            material: background.0.clone(),
+            transform: Transform::from_xyz(0., 0., 0.5),
+            ..Default::default()
+        })
+        .insert(ErrorScreenMarker)
+        .with_children(|cmds| {
+            let title = Text::with_section(
+                message.title,
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 60.,
+                    color: Color::RED,
+                },
+                alignment,
+            );
+            cmds.spawn_bundle(Text2dBundle {
+                text: title,
+                transform: Transform::from_xyz(0., 200., 0.001),
+                ..Default::default()
+            });
+            let details = Text::with_section(
+                message.details,
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 24.,
+                    color: light_text_color(),
+                },
+                alignment,
+            );
+            cmds.spawn_bundle(Text2dBundle {
+                text: details,
+                transform: Transform::from_xyz(0., 0., 0.001),
+                ..Default::default()
+            });
+            let ok = Text::with_section(
+                "OK".to_string(),
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 48.,
+                    color: light_text_color(),
+                },
+                alignment,
+            );
+            cmds.spawn_bundle(Text2dBundle {
+                text: ok,
+                transform: Transform::from_xyz(0., -250., 0.001),
+                ..Default::default()
+            })
+            .with_children(|cmds| {
+                cmds.spawn_bundle(MyButtonBundle {
+                    button: MyButton {
+                        size: Vec2::new(140., 60.),
+                        id: ClickedOk,
+                    },
+                    transform: Transform::from_xyz(0., 0., 0.0001),
+                    ..Default::default()
+                });
+            });
+        });
+}
+
+fn clicked_ok(
+    mut event_reader: EventReader<ClickedButtonEvent<ClickedOk>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if event_reader.iter().next().is_some() {
+        log::debug!("moving back to menu");
+        state
+            .set(GameState::MainMenu)
+            .expect("cant move back from error screen");
+    }
+}
+
+impl FromWorld for BackgroundMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world
+            .get_resource_mut::<Assets<ColorMaterial>>()
+            .expect("no materials");
+        let handle = materials.add(Color::rgb(0.05, 0.05, 0.05).into());
+        BackgroundMaterial(handle)
+    }
+}
+
+pub struct ErrorScreenPlugin;
+impl Plugin for ErrorScreenPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Option<ErrorMessage>>()
+            .init_resource::<BackgroundMaterial>()
+            .add_system_set(
+                SystemSet::on_update(GameState::MainMenu).with_system(show_pending_error.system()),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::ErrorScreen).with_system(setup.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::ErrorScreen)
+                    .with_system(clicked_ok.system().after("button_click"))
+                    .with_system(change_camera_scale_from_resize.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::ErrorScreen)
+                    .with_system(cleanup_system::<ErrorScreenMarker>.system()),
+            );
+        register_my_button::<ClickedOk>(app, GameState::ErrorScreen);
+    }
+}
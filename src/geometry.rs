@@ -0,0 +1,31 @@
+use bevy::math::{Mat2, Vec2};
+
+pub const PLAYER_SIZE: f32 = 32.;
+
+/// Triangle of a camera cone relative to the camera, angles in radians.
+pub fn cone_points(start_angle: f32, end_angle: f32, radius: f32) -> [Vec2; 3] {
+    let ray = Vec2::new(radius, 0.);
+    [
+        Vec2::ZERO,
+        Mat2::from_angle(start_angle) * ray,
+        Mat2::from_angle(end_angle) * ray,
+    ]
+}
+
+// https://stackoverflow.com/questions/2049582/how-to-determine-if-a-point-is-in-a-2d-triangle
+pub fn is_in_triangle(s: Vec2, triangle: [Vec2; 3]) -> bool {
+    let [a, b, c] = triangle;
+    let as_x = s.x - a.x;
+    let as_y = s.y - a.y;
+
+    let s_ab = (b.x - a.x) * as_y - (b.y - a.y) * as_x > 0.;
+    let s_ac = (c.x - a.x) * as_y - (c.y - a.y) * as_x > 0.;
+
+    if s_ac == s_ab {
+        return false;
+    }
+
+    let last = (c.x - b.x) * (s.y - b.y) - (c.y - b.y) * (s.x - b.x) > 0.;
+
+    last == s_ab
+}
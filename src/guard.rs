@@ -0,0 +1,237 @@
+use bevy::{
+    math::{Vec2, Vec3Swizzles},
+    prelude::*,
+};
+
+use crate::{
+    boundaries::Boundaries,
+    camera_enemy::{spawn_vision, Camera},
+    geometry::PLAYER_SIZE,
+    movement::Velocity,
+    pathfinding::NavGrid,
+    perlin::PerlinPipelineHandle,
+    GameState,
+};
+
+// how long a guard can stand against a wall before it looks for a way around
+const STUCK_TIME: f32 = 0.3;
+
+#[derive(Debug, Clone)]
+pub struct GuardSpawn {
+    /// First waypoint is where the guard starts.
+    pub waypoints: Vec<Vec2>,
+    /// Walk the route in circles instead of going back and forth.
+    pub looped: bool,
+    pub speed: f32,
+    pub radius: f32,
+    // in radians
+    pub fov: f32,
+}
+
+#[derive(Debug)]
+struct Guard {
+    waypoints: Vec<Vec2>,
+    looped: bool,
+    speed: f32,
+    next: usize,
+    forward: bool,
+    /// Points around an obstacle to walk through before heading for the next waypoint again.
+    detour: Vec<Vec2>,
+    last_pos: Vec2,
+    stuck_for: f32,
+}
+
+impl Guard {
+    fn new(spawn: &GuardSpawn) -> Self {
+        Guard {
+            waypoints: spawn.waypoints.clone(),
+            looped: spawn.looped,
+            speed: spawn.speed,
+            next: 1 % spawn.waypoints.len(),
+            forward: true,
+            detour: vec![],
+            last_pos: spawn.waypoints[0],
+            stuck_for: 0.,
+        }
+    }
+
+    /// Direction to walk in this frame. Once the guard stops making progress it takes a detour
+    /// over the nav grid, and skips the waypoint if there is no way to it.
+    fn steer(&mut self, pos: Vec2, delta: f32, nav_grid: &NavGrid) -> Vec2 {
+        let step = self.speed * delta;
+        if (pos - self.last_pos).length() < step / 2. {
+            self.stuck_for += delta;
+        } else {
+            self.stuck_for = 0.;
+        }
+        self.last_pos = pos;
+        if self
+            .detour
+            .first()
+            .map_or(false, |point| (*point - pos).length() <= step)
+        {
+            self.detour.remove(0);
+        }
+        if self.detour.is_empty() && (self.waypoints[self.next] - pos).length() <= step {
+            self.advance();
+        }
+        if self.stuck_for >= STUCK_TIME {
+            self.stuck_for = 0.;
+            match nav_grid.find_path(pos, self.waypoints[self.next]) {
+                Some(path) => self.detour = path,
+                None => {
+                    log::debug!("no way to {}", self.waypoints[self.next]);
+                    self.detour.clear();
+                    self.advance();
+                }
+            }
+        }
+        let target = self
+            .detour
+            .first()
+            .copied()
+            .unwrap_or(self.waypoints[self.next]);
+        (target - pos).normalize_or_zero()
+    }
+
+    fn advance(&mut self) {
+        let last = self.waypoints.len() - 1;
+        if self.looped {
+            self.next = (self.next + 1) % self.waypoints.len();
+            return;
+        }
+        if self.forward && self.next == last {
+            self.forward = false;
+        } else if !self.forward && self.next == 0 {
+            self.forward = true;
+        }
+        if self.forward {
+            self.next += 1;
+        } else {
+            self.next -= 1;
+        }
+    }
+}
+
+struct GuardTexture(Handle<ColorMaterial>);
+
+fn spawn_guards(
+    mut commands: Commands,
+    spawns: Res<Vec<GuardSpawn>>,
+    pp_handle: Res<PerlinPipelineHandle>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    boundaries: Res<Boundaries>,
+    texture: Res<GuardTexture>,
+) {
+    for spawn in spawns.iter() {
+        let start = if let Some(x) = spawn.waypoints.first() {
+            *x
+        } else {
+            continue;
+        };
+        let mut camera = Camera::new(-spawn.fov / 2., spawn.fov / 2., spawn.radius, None);
+        if let Some(next) = spawn.waypoints.get(1) {
+            let dir = *next - start;
+            camera.look_at(dir.y.atan2(dir.x));
+        }
+        let entity = spawn_vision(
+            &mut commands,
+            camera,
+            start.extend(0.2),
+            &pp_handle,
+            &mut meshes,
+            &boundaries,
+        );
+        commands
+            .entity(entity)
+            .insert(Guard::new(spawn))
+            .insert(Velocity::default())
+            .with_children(|ec| {
+                ec.spawn_bundle(SpriteBundle {
+                    sprite: Sprite::new(Vec2::splat(PLAYER_SIZE)),
+                    material: texture.0.clone(),
+                    transform: Transform::from_xyz(0., 0., 0.3),
+                    ..Default::default()
+                });
+            });
+    }
+}
+
+fn patrol(
+    guards: Query<(&mut Guard, &Transform, &mut Velocity, &mut Camera)>,
+    time: Res<Time>,
+    nav_grid: Res<NavGrid>,
+) {
+    let delta = time.delta_seconds();
+    guards.for_each_mut(|(mut guard, tr, mut velocity, mut camera)| {
+        if guard.waypoints.len() < 2 {
+            velocity.0 = Vec2::ZERO;
+            return;
+        }
+        let dir = guard.steer(tr.translation.xy(), delta, &nav_grid);
+        velocity.0 = dir * guard.speed;
+        if dir != Vec2::ZERO {
+            camera.look_at(dir.y.atan2(dir.x));
+        }
+    });
+}
+
+impl FromWorld for GuardTexture {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world
+            .get_resource_mut::<Assets<ColorMaterial>>()
+            .expect("no materials");
+        let handle = materials.add(Color::rgb(0.6, 0.1, 0.1).into());
+        GuardTexture(handle)
+    }
+}
+
+pub struct GuardPlugin;
+impl Plugin for GuardPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Vec<GuardSpawn>>()
+            .init_resource::<GuardTexture>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Level).with_system(spawn_guards.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Level)
+                    .with_system(patrol.system().label("control")),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+
+    use super::*;
+    use crate::{
+        map_validation::{GUARD_FOV, GUARD_RADIUS, GUARD_SPEED},
+        movement::step,
+    };
+
+    #[test]
+    fn walks_around_a_wall() {
+        // a wall right across the straight line between the waypoints
+        let bounds = Boundaries::new(vec![(Vec3::new(160., 160., 0.), Vec2::new(32., 160.))]);
+        let nav_grid = NavGrid::from_boundaries(10, 10, Vec2::splat(32.), &bounds);
+        let mut guard = Guard::new(&GuardSpawn {
+            waypoints: vec![Vec2::new(64., 160.), Vec2::new(256., 160.)],
+            looped: false,
+            speed: GUARD_SPEED,
+            radius: GUARD_RADIUS,
+            fov: GUARD_FOV.to_radians(),
+        });
+        let delta = 1. / 60.;
+        let mut pos = guard.waypoints[0];
+        for _ in 0..10 * 60 {
+            let dir = guard.steer(pos, delta, &nav_grid);
+            pos = step(&bounds, pos, dir * guard.speed, delta);
+            if guard.next == 0 {
+                return;
+            }
+        }
+        panic!("guard stuck at {}", pos);
+    }
+}
@@ -1,10 +1,11 @@
 use bevy::{log, prelude::*};
-use itertools::Itertools;
+use rand::thread_rng;
 
 use crate::{
     button::{register_my_button, ClickedButtonEvent, MyButton, MyButtonBundle},
     cleanup::cleanup_system,
-    items::{Item, PlayerItems, Slot},
+    items::{Craft, PlayerItems, Slot},
+    loot::LootTables,
     main_menu_ui::{change_camera_scale_from_resize, light_text_color},
     perlin::{PerlinBundle, PerlinPipelineHandle},
     GameState, RobotoFont,
@@ -18,6 +19,11 @@ struct ClickedItem(usize);
 #[derive(Debug, Clone, Copy, Default)]
 struct DeletedItem(usize);
 
+#[derive(Debug, Clone, Copy, Default)]
+struct CraftedItem(usize, Craft);
+
+struct ShardsLabel;
+
 struct UiTexture(Handle<ColorMaterial>);
 struct UiCardTexture(Handle<ColorMaterial>);
 
@@ -36,6 +42,7 @@ pub struct ViewInvSlot(pub Slot, pub u32);
 struct CurrentItemsView(Entity);
 
 const ITEMS_ON_PAGE: u32 = 9;
+const CRAFTS: [Craft; 3] = [Craft::Reroll, Craft::Augment, Craft::Chaos];
 
 fn setup(
     mut commands: Commands,
@@ -43,6 +50,7 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     ui_texture: Res<UiTexture>,
     font: Res<RobotoFont>,
+    items: Res<PlayerItems>,
 ) {
     let v_pos = vec![
         [-2000., -2000.],
@@ -98,6 +106,25 @@ fn setup(
                     ..Default::default()
                 });
 
+                let shards = Text::with_section(
+                    shards_text(items.shards),
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: 24.0,
+                        color: light_text_color(),
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                );
+                cmds.spawn_bundle(Text2dBundle {
+                    text: shards,
+                    transform: Transform::from_xyz(430., 381., 0.001),
+                    ..Default::default()
+                })
+                .insert(ShardsLabel);
+
                 // low buttons
                 cmds.spawn()
                     .insert(Transform::from_xyz(0., -423., 0.001))
@@ -191,13 +218,19 @@ fn setup(
         });
 }
 
+fn shards_text(shards: u32) -> String {
+    format!("Shards: {}", shards)
+}
+
 fn draw_slot(
     cmds: &mut ChildBuilder,
-    item: &Item,
+    items: &PlayerItems,
+    slot: Slot,
     font: &RobotoFont,
     index: usize,
     is_equipped: bool,
 ) {
+    let item = &items.slot_items(slot).available[index];
     let color = light_text_color();
 
     //name
@@ -220,12 +253,7 @@ fn draw_slot(
     });
 
     //mods
-    let text = item
-        .mods
-        .iter()
-        .map(|item_mod| item_mod.to_string())
-        .collect_vec()
-        .join("\n");
+    let text = item.description();
     let item_mods = Text::with_section(
         text,
         TextStyle {
@@ -244,6 +272,45 @@ fn draw_slot(
         ..Default::default()
     });
 
+    for (i, craft) in CRAFTS.iter().enumerate() {
+        if item.can_craft(*craft).is_err() {
+            continue;
+        }
+        // shown even when there are not enough shards, so the costs are known
+        let color = if items.can_craft(slot, index, *craft).is_ok() {
+            color
+        } else {
+            Color::GRAY
+        };
+        let label = Text::with_section(
+            format!("{} ({})", craft.name(), craft.cost()),
+            TextStyle {
+                font: font.0.clone(),
+                font_size: 14.,
+                color,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            },
+        );
+        cmds.spawn_bundle(Text2dBundle {
+            text: label,
+            transform: Transform::from_xyz((i as f32 - 1.) * 108., -90., 0.001),
+            ..Default::default()
+        })
+        .with_children(|cmds| {
+            cmds.spawn_bundle(MyButtonBundle {
+                button: MyButton {
+                    size: Vec2::new(100., 22.),
+                    id: CraftedItem(index, *craft),
+                },
+                transform: Transform::from_xyz(0., 0., 0.001),
+                ..Default::default()
+            });
+        });
+    }
+
     if !is_equipped {
         // buttons
         let select = Text::with_section(
@@ -326,10 +393,7 @@ fn dispatch_items(
         .with_children(|cmds| {
             let min_el = view.1 as usize;
             let slot_items = items.slot_items(view.0);
-            for (orig_i, item) in slot_items
-                .available
-                .iter()
-                .enumerate()
+            for orig_i in (0..slot_items.available.len())
                 .skip(min_el)
                 .take(ITEMS_ON_PAGE as usize)
             {
@@ -345,7 +409,8 @@ fn dispatch_items(
                     ..Default::default()
                 })
                 .with_children(|cmds| {
-                    draw_slot(cmds, item, &font, orig_i, slot_items.equipped == orig_i);
+                    let is_equipped = slot_items.equipped == orig_i;
+                    draw_slot(cmds, &items, view.0, &font, orig_i, is_equipped);
                 });
             }
         })
@@ -419,6 +484,35 @@ fn clicked_delete(
     }
 }
 
+fn clicked_craft(
+    mut event_reader: EventReader<ClickedButtonEvent<CraftedItem>>,
+    cur_view: Res<ViewInvSlot>,
+    mut items: ResMut<PlayerItems>,
+    tables: Res<LootTables>,
+) {
+    if let Some(ClickedButtonEvent(CraftedItem(index, craft))) = event_reader.iter().next() {
+        // checked first, a failed craft should not count as a change to save
+        if let Err(e) = items.can_craft(cur_view.0, *index, *craft) {
+            log::debug!("cant {:?}: {}", craft, e);
+            return;
+        }
+        log::debug!("crafting {:?}", craft);
+        // not the loot rng, crafting should not change what a seed drops
+        let mut rng = thread_rng();
+        let table = tables.crafting_table();
+        if let Err(e) = items.craft(cur_view.0, *index, *craft, &mut rng, table) {
+            log::warn!("cant {:?}: {}", craft, e);
+        }
+    }
+}
+
+fn update_shards(items: Res<PlayerItems>, labels: Query<&mut Text, With<ShardsLabel>>) {
+    if !items.is_changed() {
+        return;
+    }
+    labels.for_each_mut(|mut text| text.sections[0].value = shards_text(items.shards));
+}
+
 impl FromWorld for UiTexture {
     fn from_world(world: &mut World) -> Self {
         let asset_server = world
@@ -484,6 +578,13 @@ impl Plugin for InventoryScreenPlugin {
                         .after("button_click")
                         .before("dispatch_inventory"),
                 )
+                .with_system(
+                    clicked_craft
+                        .system()
+                        .after("button_click")
+                        .before("dispatch_inventory"),
+                )
+                .with_system(update_shards.system().after("dispatch_inventory"))
                 .with_system(dispatch_items.system().label("dispatch_inventory"))
                 .with_system(change_camera_scale_from_resize.system()),
         )
@@ -496,5 +597,6 @@ impl Plugin for InventoryScreenPlugin {
         register_my_button::<ClickedPrev>(app, GameState::InventoryScreen);
         register_my_button::<ClickedItem>(app, GameState::InventoryScreen);
         register_my_button::<DeletedItem>(app, GameState::InventoryScreen);
+        register_my_button::<CraftedItem>(app, GameState::InventoryScreen);
     }
 }
@@ -1,16 +1,16 @@
 use std::{
     fmt::Display,
-    fs::File,
-    io::Read,
     iter::{once, repeat},
     ops::Range,
 };
 
-use bevy::{log, prelude::*};
+use bevy::log;
 use itertools::Itertools;
-use rand::{prelude::SliceRandom, thread_rng, Rng};
+use rand::{prelude::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
 
+use crate::{effects::Effect, loot::LootTable, uniques::Uniques};
+
 #[derive(Debug, Default)]
 pub struct PlayerStatsMods {
     pub light_radius: f32,
@@ -18,13 +18,129 @@ pub struct PlayerStatsMods {
     pub duration: f32,
     pub movement_speed: f32,
     pub cooldown_reduction: f32,
+    pub dash_no_mana_cost: bool,
+    /// Flat reduction of every skill's mana cost.
+    pub mana_cost: f32,
+    /// Flat mana per second on top of the base regeneration.
+    pub mana_regen: f32,
+    pub total_mana: f32,
+    pub dash_charges: u32,
+    pub dash_teleport: bool,
+}
+
+impl PlayerStatsMods {
+    pub fn scale_light_radius(&self, base: f32) -> f32 {
+        base * (1. + self.light_radius)
+    }
+
+    pub fn scale_area(&self, base: f32) -> f32 {
+        base * (1. + self.area_of_effect)
+    }
+
+    pub fn scale_duration(&self, base: f32) -> f32 {
+        base * (1. + self.duration)
+    }
+
+    pub fn scale_movement_speed(&self, base: f32) -> f32 {
+        base * (1. + self.movement_speed)
+    }
+
+    /// Reduction is a rate increase, so 100% halves the cooldown instead of removing it.
+    pub fn scale_cooldown(&self, base: f32) -> f32 {
+        base / (1. + self.cooldown_reduction)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Item {
     pub name: String,
     pub slot: Slot,
+    pub rarity: Rarity,
     pub mods: Vec<Mod>,
+    /// Only uniques have them.
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    #[serde(default)]
+    pub set: Option<ItemSet>,
+}
+
+impl Item {
+    pub fn can_craft(&self, craft: Craft) -> Result<(), CraftError> {
+        match craft {
+            _ if self.rarity == Rarity::Unique => Err(CraftError::Unique),
+            Craft::Reroll | Craft::Chaos if self.mods.is_empty() => Err(CraftError::NoMods),
+            // behaviour mods have a single value, rerolling them would only waste shards
+            Craft::Reroll if self.mods.iter().all(|a_mod| a_mod.kind.is_behaviour()) => {
+                Err(CraftError::NothingToReroll)
+            }
+            Craft::Augment if self.mods.len() >= MAX_CRAFTED_MODS => Err(CraftError::FullOfMods),
+            _ => Ok(()),
+        }
+    }
+
+    /// Mods are rolled from the table's pool, the same way `generate` does.
+    fn craft(
+        &mut self,
+        craft: Craft,
+        rng: &mut impl Rng,
+        table: &LootTable,
+    ) -> Result<(), CraftError> {
+        self.can_craft(craft)?;
+        match craft {
+            Craft::Reroll => {
+                let mut rerollable = self
+                    .mods
+                    .iter_mut()
+                    .filter(|a_mod| !a_mod.kind.is_behaviour())
+                    .collect_vec();
+                let a_mod = rerollable
+                    .choose_mut(rng)
+                    .expect("item has no rerollable mods");
+                a_mod.value = a_mod.kind.roll(rng);
+            }
+            Craft::Augment => {
+                let pool = table
+                    .mods
+                    .iter()
+                    .filter(|pooled| !self.mods.iter().any(|a_mod| a_mod.kind == pooled.kind))
+                    .collect_vec();
+                let kind = pool
+                    .choose_weighted(rng, |pooled| pooled.weight)
+                    .map_err(|_| CraftError::FullOfMods)?
+                    .kind;
+                self.mods.push(Mod {
+                    value: kind.roll(rng),
+                    kind,
+                });
+                self.rarity = match self.rarity {
+                    Rarity::Common => Rarity::Magic,
+                    _ => Rarity::Rare,
+                };
+                self.name = item_name(self.rarity, self.slot, self.set);
+            }
+            Craft::Chaos => {
+                self.mods = table
+                    .mods
+                    .choose_multiple_weighted(rng, self.mods.len(), |pooled| pooled.weight)
+                    .expect("no mods in the loot table")
+                    .map(|pooled| Mod {
+                        value: pooled.kind.roll(rng),
+                        kind: pooled.kind,
+                    })
+                    .collect_vec();
+            }
+        }
+        Ok(())
+    }
+
+    /// Mods and effects, one per line.
+    pub fn description(&self) -> String {
+        self.mods
+            .iter()
+            .map(|a_mod| a_mod.to_string())
+            .chain(self.effects.iter().map(|effect| effect.to_string()))
+            .join("\n")
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -35,7 +151,19 @@ pub struct Mod {
 
 impl Display for Mod {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}% {}", (self.value * 100.) as i32, self.kind.suffix())
+        if self.kind.is_behaviour() {
+            return write!(f, "{}", self.kind.suffix());
+        }
+        match self.kind {
+            ModKind::DashCharge => {
+                write!(f, "Dash has {} {}", self.value as i32, self.kind.suffix())
+            }
+            ModKind::ManaCost => write!(f, "-{} {}", self.value as i32, self.kind.suffix()),
+            ModKind::ManaRegen | ModKind::TotalMana => {
+                write!(f, "+{} {}", self.value as i32, self.kind.suffix())
+            }
+            _ => write!(f, "{}% {}", (self.value * 100.) as i32, self.kind.suffix()),
+        }
     }
 }
 
@@ -46,6 +174,12 @@ pub enum ModKind {
     Duration,
     MovementSpeed,
     CooldownReduction,
+    DashNoManaCost,
+    ManaCost,
+    ManaRegen,
+    TotalMana,
+    DashCharge,
+    DashBecomesTeleport,
 }
 
 impl Default for ModKind {
@@ -62,21 +196,174 @@ impl ModKind {
             ModKind::Duration => "increased duration",
             ModKind::MovementSpeed => "increased movement speed",
             ModKind::CooldownReduction => "increased cooldown reduction",
+            ModKind::DashNoManaCost => "Dash costs no mana",
+            ModKind::ManaCost => "mana cost",
+            ModKind::ManaRegen => "mana regen",
+            ModKind::TotalMana => "total mana",
+            ModKind::DashCharge => "more charge",
+            ModKind::DashBecomesTeleport => "Dash becomes Teleport",
         }
     }
 
-    fn range(&self) -> Range<f32> {
+    /// Lowest tier first, the better tiers are rarer.
+    pub fn tiers(&self) -> &'static [ModTier] {
         match self {
-            ModKind::LightRadius => 0.1..0.33,
-            ModKind::AreaOfEffect => 0.1..0.33,
-            ModKind::Duration => 0.1..0.2,
-            ModKind::MovementSpeed => 0.05..0.2,
-            ModKind::CooldownReduction => 0.1..0.3,
+            ModKind::LightRadius | ModKind::AreaOfEffect => &RADIUS_TIERS,
+            ModKind::Duration => &DURATION_TIERS,
+            ModKind::MovementSpeed => &MOVEMENT_SPEED_TIERS,
+            ModKind::CooldownReduction => &COOLDOWN_TIERS,
+            ModKind::ManaCost => &MANA_COST_TIERS,
+            ModKind::ManaRegen => &MANA_REGEN_TIERS,
+            ModKind::TotalMana => &TOTAL_MANA_TIERS,
+            ModKind::DashNoManaCost | ModKind::DashCharge | ModKind::DashBecomesTeleport => {
+                &SINGLE_TIER
+            }
         }
     }
+
+    /// Changes how a skill works instead of adding to a stat, has no value to show.
+    pub fn is_behaviour(&self) -> bool {
+        matches!(self, ModKind::DashNoManaCost | ModKind::DashBecomesTeleport)
+    }
+
+    fn roll(&self, rng: &mut impl Rng) -> f32 {
+        let range = self
+            .tiers()
+            .choose_weighted(rng, |tier| tier.weight)
+            .expect("mod has no tiers")
+            .range
+            .clone();
+        match self {
+            // flags and charges, nothing to roll
+            _ if self.is_behaviour() => range.start,
+            ModKind::DashCharge => range.start,
+            // flat mods are shown as whole numbers
+            ModKind::ManaCost | ModKind::ManaRegen | ModKind::TotalMana => {
+                rng.gen_range(range).round()
+            }
+            _ => rng.gen_range(range),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModTier {
+    pub range: Range<f32>,
+    pub weight: u32,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+const RADIUS_TIERS: [ModTier; 3] = [
+    ModTier {
+        range: 0.1..0.17,
+        weight: 6,
+    },
+    ModTier {
+        range: 0.17..0.25,
+        weight: 3,
+    },
+    ModTier {
+        range: 0.25..0.33,
+        weight: 1,
+    },
+];
+
+const DURATION_TIERS: [ModTier; 3] = [
+    ModTier {
+        range: 0.1..0.13,
+        weight: 6,
+    },
+    ModTier {
+        range: 0.13..0.17,
+        weight: 3,
+    },
+    ModTier {
+        range: 0.17..0.2,
+        weight: 1,
+    },
+];
+
+const MOVEMENT_SPEED_TIERS: [ModTier; 3] = [
+    ModTier {
+        range: 0.05..0.1,
+        weight: 6,
+    },
+    ModTier {
+        range: 0.1..0.15,
+        weight: 3,
+    },
+    ModTier {
+        range: 0.15..0.2,
+        weight: 1,
+    },
+];
+
+const COOLDOWN_TIERS: [ModTier; 3] = [
+    ModTier {
+        range: 0.1..0.17,
+        weight: 6,
+    },
+    ModTier {
+        range: 0.17..0.24,
+        weight: 3,
+    },
+    ModTier {
+        range: 0.24..0.3,
+        weight: 1,
+    },
+];
+
+// flat tiers are rounded, the bounds sit between whole numbers
+const MANA_COST_TIERS: [ModTier; 3] = [
+    ModTier {
+        range: 1.0..1.5,
+        weight: 6,
+    },
+    ModTier {
+        range: 1.5..2.5,
+        weight: 3,
+    },
+    ModTier {
+        range: 2.5..3.0,
+        weight: 1,
+    },
+];
+
+const MANA_REGEN_TIERS: [ModTier; 3] = [
+    ModTier {
+        range: 1.0..2.5,
+        weight: 6,
+    },
+    ModTier {
+        range: 2.5..4.0,
+        weight: 3,
+    },
+    ModTier {
+        range: 4.0..5.0,
+        weight: 1,
+    },
+];
+
+const TOTAL_MANA_TIERS: [ModTier; 3] = [
+    ModTier {
+        range: 5.0..8.5,
+        weight: 6,
+    },
+    ModTier {
+        range: 8.5..12.0,
+        weight: 3,
+    },
+    ModTier {
+        range: 12.0..15.0,
+        weight: 1,
+    },
+];
+
+const SINGLE_TIER: [ModTier; 1] = [ModTier {
+    range: 1.0..1.0,
+    weight: 1,
+}];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Slot {
     Head,
     Cloak,
@@ -84,31 +371,120 @@ pub enum Slot {
     Boots,
 }
 
+/// Ordered from the most common.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Rarity {
+    Common,
+    Magic,
+    Rare,
+    /// Hand made in `assets/uniques.json`.
+    Unique,
+}
+
 impl Slot {
-    fn common_name(&self) -> String {
+    fn base_name(&self) -> &'static str {
+        match self {
+            Slot::Head => "Mask",
+            Slot::Cloak => "Cloak",
+            Slot::Lockpick => "Lockpick",
+            Slot::Boots => "Boots",
+        }
+    }
+}
+
+/// Set pieces are named after their set whatever the rarity.
+fn item_name(rarity: Rarity, slot: Slot, set: Option<ItemSet>) -> String {
+    match set {
+        Some(set) => format!("{} {}", set.name(), slot.base_name()),
+        None => rarity.item_name(slot),
+    }
+}
+
+impl Rarity {
+    pub fn item_name(&self, slot: Slot) -> String {
+        let base = slot.base_name();
+        match self {
+            Rarity::Common => base.to_string(),
+            Rarity::Magic => format!("Magic {}", base),
+            Rarity::Rare | Rarity::Unique => format!("Great {}", base),
+        }
+    }
+
+    /// Shards for deleting an item.
+    pub fn salvage_value(&self) -> u32 {
         match self {
-            Slot::Head => "Mask".to_string(),
-            Slot::Cloak => "Cloak".to_string(),
-            Slot::Lockpick => "Lockpick".to_string(),
-            Slot::Boots => "Boots".to_string(),
+            Rarity::Common => 1,
+            Rarity::Magic => 3,
+            Rarity::Rare => 8,
+            Rarity::Unique => 20,
         }
     }
 
-    fn magic_name(&self) -> String {
+    /// Rarity of items made before it was saved, back when it came from the number of mods.
+    pub fn from_mod_count(count: usize) -> Self {
+        match count {
+            0 | 1 => Rarity::Common,
+            2 | 3 => Rarity::Magic,
+            _ => Rarity::Rare,
+        }
+    }
+}
+
+impl Default for Rarity {
+    fn default() -> Self {
+        Rarity::Common
+    }
+}
+
+/// One piece per slot, so a full set is 4 items.
+pub const SET_PIECES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ItemSet {
+    Nightstalker,
+    Arcanist,
+}
+
+/// Granted while at least `pieces` items of the set are equipped.
+#[derive(Debug, Clone)]
+pub struct SetBonus {
+    pub pieces: usize,
+    pub a_mod: Mod,
+}
+
+impl Display for SetBonus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}) {}", self.pieces, self.a_mod)
+    }
+}
+
+fn set_bonus(pieces: usize, kind: ModKind, value: f32) -> SetBonus {
+    SetBonus {
+        pieces,
+        a_mod: Mod { kind, value },
+    }
+}
+
+impl ItemSet {
+    pub fn name(&self) -> &'static str {
         match self {
-            Slot::Head => "Magic Mask".to_string(),
-            Slot::Cloak => "Magic Cloak".to_string(),
-            Slot::Lockpick => "Magic Lockpick".to_string(),
-            Slot::Boots => "Magic Boots".to_string(),
+            ItemSet::Nightstalker => "Nightstalker",
+            ItemSet::Arcanist => "Arcanist",
         }
     }
 
-    fn rare_name(&self) -> String {
+    pub fn bonuses(&self) -> Vec<SetBonus> {
         match self {
-            Slot::Head => "Great Mask".to_string(),
-            Slot::Cloak => "Great Cloak".to_string(),
-            Slot::Lockpick => "Great Lockpick".to_string(),
-            Slot::Boots => "Great Boots".to_string(),
+            ItemSet::Nightstalker => vec![
+                set_bonus(2, ModKind::MovementSpeed, 0.1),
+                set_bonus(4, ModKind::DashCharge, 1.),
+                set_bonus(4, ModKind::DashBecomesTeleport, 1.),
+            ],
+            ItemSet::Arcanist => vec![
+                set_bonus(2, ModKind::TotalMana, 20.),
+                set_bonus(4, ModKind::ManaRegen, 5.),
+                set_bonus(4, ModKind::CooldownReduction, 0.2),
+            ],
         }
     }
 }
@@ -138,21 +514,27 @@ pub struct PlayerItems {
     pub cloak: SlotItems,
     pub lockpick: SlotItems,
     pub boots: SlotItems,
+    /// Crafting currency, gained by deleting items.
+    pub shards: u32,
 }
 
 impl PlayerItems {
+    /// Includes the set bonuses.
     pub fn stats(&self) -> PlayerStatsMods {
         let mut stats = PlayerStatsMods::default();
-        let slots = [&self.head, &self.cloak, &self.lockpick, &self.boots];
-        for slot in slots {
-            for a_mod in &slot.equipped().mods {
-                match a_mod.kind {
-                    ModKind::LightRadius => stats.light_radius += a_mod.value,
-                    ModKind::AreaOfEffect => stats.area_of_effect += a_mod.value,
-                    ModKind::Duration => stats.duration += a_mod.value,
-                    ModKind::MovementSpeed => stats.movement_speed += a_mod.value,
-                    ModKind::CooldownReduction => stats.cooldown_reduction += a_mod.value,
-                }
+        for a_mod in self.all_equipped_mods() {
+            match a_mod.kind {
+                ModKind::LightRadius => stats.light_radius += a_mod.value,
+                ModKind::AreaOfEffect => stats.area_of_effect += a_mod.value,
+                ModKind::Duration => stats.duration += a_mod.value,
+                ModKind::MovementSpeed => stats.movement_speed += a_mod.value,
+                ModKind::CooldownReduction => stats.cooldown_reduction += a_mod.value,
+                ModKind::DashNoManaCost => stats.dash_no_mana_cost = true,
+                ModKind::ManaCost => stats.mana_cost += a_mod.value,
+                ModKind::ManaRegen => stats.mana_regen += a_mod.value,
+                ModKind::TotalMana => stats.total_mana += a_mod.value,
+                ModKind::DashCharge => stats.dash_charges += a_mod.value as u32,
+                ModKind::DashBecomesTeleport => stats.dash_teleport = true,
             }
         }
         stats
@@ -191,10 +573,52 @@ impl PlayerItems {
         slot_items.available.iter()
     }
 
+    /// Equipped pieces of every set that has any, by set name.
+    pub fn equipped_sets(&self) -> Vec<(ItemSet, usize)> {
+        self.all_equipped_items()
+            .filter_map(|item| item.set)
+            .counts()
+            .into_iter()
+            .sorted_by_key(|(set, _)| set.name())
+            .collect()
+    }
+
+    pub fn active_set_bonuses(&self) -> Vec<SetBonus> {
+        self.equipped_sets()
+            .into_iter()
+            .flat_map(|(set, pieces)| {
+                set.bonuses()
+                    .into_iter()
+                    .filter(move |bonus| pieces >= bonus.pieces)
+            })
+            .collect()
+    }
+
+    /// A line per worn set followed by the bonuses it grants, empty without sets.
+    pub fn set_description(&self) -> String {
+        self.equipped_sets()
+            .into_iter()
+            .flat_map(|(set, pieces)| {
+                let bonuses = set
+                    .bonuses()
+                    .into_iter()
+                    .filter(move |bonus| pieces >= bonus.pieces)
+                    .map(|bonus| bonus.to_string());
+                once(format!("{} {}/{}", set.name(), pieces, SET_PIECES)).chain(bonuses)
+            })
+            .join("\n")
+    }
+
+    /// Set bonuses are added in like the mods of another item.
     pub fn all_equipped_mods(&self) -> impl Iterator<Item = Mod> + '_ {
+        let set_mods = self
+            .active_set_bonuses()
+            .into_iter()
+            .map(|bonus| bonus.a_mod);
         let grouped = self
             .all_equipped_items()
             .flat_map(|item| item.mods.clone())
+            .chain(set_mods)
             .into_grouping_map_by(|a_mod| a_mod.kind);
         grouped
             .fold(Mod::default(), |mut acc, kind, next| {
@@ -215,7 +639,30 @@ impl PlayerItems {
         if index < slot_items.equipped {
             slot_items.equipped -= 1;
         }
-        slot_items.available.remove(index);
+        let salvaged = slot_items.available.remove(index);
+        self.shards += salvaged.rarity.salvage_value();
+    }
+
+    pub fn can_craft(&self, slot: Slot, index: usize, craft: Craft) -> Result<(), CraftError> {
+        self.slot_items(slot).available[index].can_craft(craft)?;
+        if self.shards < craft.cost() {
+            return Err(CraftError::NotEnoughShards);
+        }
+        Ok(())
+    }
+
+    pub fn craft(
+        &mut self,
+        slot: Slot,
+        index: usize,
+        craft: Craft,
+        rng: &mut impl Rng,
+        table: &LootTable,
+    ) -> Result<(), CraftError> {
+        self.can_craft(slot, index, craft)?;
+        self.slot_items_mut(slot).available[index].craft(craft, rng, table)?;
+        self.shards -= craft.cost();
+        Ok(())
     }
 
     pub fn equip_on_slot(&mut self, slot: Slot, index: usize) {
@@ -223,65 +670,142 @@ impl PlayerItems {
     }
 }
 
-const RARE_CHANCE: f32 = 0.9;
-const MAGIC_CHANCE: f32 = 0.6;
+/// Most mods crafting can put on an item, as many as a rare drops with.
+pub const MAX_CRAFTED_MODS: usize = 4;
 
-const KINDS: [ModKind; 5] = [
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Craft {
+    /// Rolls the value of one random mod again.
+    Reroll,
+    /// Adds a mod the item does not have yet, raising its rarity.
+    Augment,
+    /// Replaces every mod, keeping how many there are.
+    Chaos,
+}
+
+impl Default for Craft {
+    fn default() -> Self {
+        Craft::Reroll
+    }
+}
+
+impl Craft {
+    /// In shards.
+    pub fn cost(&self) -> u32 {
+        match self {
+            Craft::Reroll => 2,
+            Craft::Augment => 6,
+            Craft::Chaos => 4,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Craft::Reroll => "Reroll",
+            Craft::Augment => "Augment",
+            Craft::Chaos => "Chaos",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CraftError {
+    NotEnoughShards,
+    /// Uniques always keep their mods.
+    Unique,
+    NoMods,
+    /// Only behaviour mods, they have nothing to reroll.
+    NothingToReroll,
+    FullOfMods,
+}
+
+impl Display for CraftError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CraftError::NotEnoughShards => write!(f, "not enough shards"),
+            CraftError::Unique => write!(f, "uniques cant be crafted"),
+            CraftError::NoMods => write!(f, "the item has no mods"),
+            CraftError::NothingToReroll => write!(f, "the item has no mods to reroll"),
+            CraftError::FullOfMods => write!(f, "the item cant take more mods"),
+        }
+    }
+}
+
+pub const KINDS: [ModKind; 11] = [
     ModKind::MovementSpeed,
     ModKind::LightRadius,
     ModKind::Duration,
     ModKind::CooldownReduction,
     ModKind::AreaOfEffect,
+    ModKind::DashNoManaCost,
+    ModKind::ManaCost,
+    ModKind::ManaRegen,
+    ModKind::TotalMana,
+    ModKind::DashCharge,
+    ModKind::DashBecomesTeleport,
 ];
 
-const SLOTS: [Slot; 4] = [Slot::Head, Slot::Cloak, Slot::Lockpick, Slot::Boots];
+pub const SLOTS: [Slot; 4] = [Slot::Head, Slot::Cloak, Slot::Lockpick, Slot::Boots];
 
-pub fn generate() -> Vec<Item> {
+/// Rolls what one treasure gives, the same rng state and tables always give the same items.
+pub fn generate(rng: &mut impl Rng, table: &LootTable, uniques: &Uniques) -> Vec<Item> {
+    // tables are checked when loaded, none of the picks below can fail
+    let count = table
+        .drops
+        .choose_weighted(rng, |drop| drop.weight)
+        .expect("no drop counts in the loot table")
+        .count;
     let mut items = vec![];
-    let mut rng = thread_rng();
-    for _ in 0..3 {
-        let mut kinds = 1;
-        let r: f32 = rng.gen();
-        if r > RARE_CHANCE {
-            kinds += 2;
-        }
-        if r > MAGIC_CHANCE {
-            kinds += 1;
+    for _ in 0..count {
+        let rarity = table
+            .rarities
+            .choose_weighted(rng, |rarity| rarity.weight)
+            .expect("no rarities in the loot table");
+        if rarity.rarity == Rarity::Unique {
+            if let Some(item) = uniques.choose(rng, &table.slots) {
+                items.push(item);
+                continue;
+            }
         }
-        let slot = *SLOTS
-            .choose_multiple(&mut rng, 1)
-            .next()
-            .expect("no slots generated");
-        let name = match kinds {
-            1 => slot.common_name(),
-            2 => slot.magic_name(),
-            4 => slot.rare_name(),
-            _ => todo!(),
-        };
-        let mods = KINDS
-            .choose_multiple(&mut rng, kinds)
-            .map(|kind| Mod {
-                value: rng.gen_range(kind.range()),
-                kind: *kind,
+        let slot = *table.slots.choose(rng).expect("no slots in the loot table");
+        let mods = table
+            .mods
+            .choose_multiple_weighted(rng, rarity.mods, |a_mod| a_mod.weight)
+            .expect("no mods in the loot table")
+            .map(|a_mod| Mod {
+                value: a_mod.kind.roll(rng),
+                kind: a_mod.kind,
             })
             .collect_vec();
-        let item = Item { name, slot, mods };
+        // a unique roll with no unique for these slots
+        let rarity = match rarity.rarity {
+            Rarity::Unique => Rarity::Rare,
+            rarity => rarity,
+        };
+        // drawn even without sets, adding sets to a table should not change what else it rolls
+        let set_roll = rng.gen::<f32>();
+        let set = if set_roll < table.set_chance {
+            table.sets.choose(rng).copied()
+        } else {
+            None
+        };
+        let item = Item {
+            name: item_name(rarity, slot, set),
+            slot,
+            rarity,
+            mods,
+            effects: vec![],
+            set,
+        };
         items.push(item);
     }
     items
 }
 
-impl FromWorld for PlayerItems {
-    fn from_world(_world: &mut World) -> Self {
-        match File::open("save.json") {
-            Ok(mut file) => {
-                let mut contents = String::new();
-                file.read_to_string(&mut contents)
-                    .expect("cant read a save");
-                serde_json::from_str(&contents).expect("cant deserialize a save")
-            }
-            Err(_) => default_items(),
-        }
+// a fresh start, the save is loaded on top of it in `save::load_save`
+impl Default for PlayerItems {
+    fn default() -> Self {
+        default_items()
     }
 }
 
@@ -289,42 +813,57 @@ fn default_items() -> PlayerItems {
     let bad_head = Item {
         name: "Mask".to_string(),
         slot: Slot::Head,
+        rarity: Rarity::Common,
         mods: vec![Mod {
             kind: ModKind::AreaOfEffect,
             value: 0.5,
         }],
+        effects: vec![],
+        set: None,
     };
     let head = Item {
         name: "Mask".to_string(),
         slot: Slot::Head,
+        rarity: Rarity::Common,
         mods: vec![Mod {
             kind: ModKind::LightRadius,
             value: 0.33,
         }],
+        effects: vec![],
+        set: None,
     };
     let cloak = Item {
         name: "Cloak".to_string(),
         slot: Slot::Cloak,
+        rarity: Rarity::Common,
         mods: vec![Mod {
             kind: ModKind::LightRadius,
             value: 0.33,
         }],
+        effects: vec![],
+        set: None,
     };
     let lockpick = Item {
         name: "Lockpick".to_string(),
         slot: Slot::Lockpick,
+        rarity: Rarity::Common,
         mods: vec![Mod {
             kind: ModKind::LightRadius,
             value: 0.33,
         }],
+        effects: vec![],
+        set: None,
     };
     let boots = Item {
         name: "Boots".to_string(),
         slot: Slot::Boots,
+        rarity: Rarity::Common,
         mods: vec![Mod {
             kind: ModKind::LightRadius,
             value: 0.33,
         }],
+        effects: vec![],
+        set: None,
     };
     let head = SlotItems {
         slot: Slot::Head,
@@ -351,5 +890,98 @@ fn default_items() -> PlayerItems {
         cloak,
         lockpick,
         boots,
+        shards: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+
+    #[test]
+    fn no_mods_keep_the_base() {
+        let stats = PlayerStatsMods::default();
+        assert_eq!(stats.scale_light_radius(300.), 300.);
+        assert_eq!(stats.scale_area(50.), 50.);
+        assert_eq!(stats.scale_duration(1.5), 1.5);
+        assert_eq!(stats.scale_movement_speed(120.), 120.);
+        assert_eq!(stats.scale_cooldown(10.), 10.);
+    }
+
+    #[test]
+    fn increases_add_to_the_base() {
+        let stats = PlayerStatsMods {
+            light_radius: 0.5,
+            area_of_effect: 0.25,
+            duration: 1.,
+            movement_speed: 0.125,
+            ..Default::default()
+        };
+        assert_eq!(stats.scale_light_radius(300.), 450.);
+        assert_eq!(stats.scale_area(50.), 62.5);
+        assert_eq!(stats.scale_duration(1.5), 3.);
+        assert_eq!(stats.scale_movement_speed(120.), 135.);
+    }
+
+    #[test]
+    fn cooldown_reduction_never_reaches_zero() {
+        let cooldown = |reduction| {
+            PlayerStatsMods {
+                cooldown_reduction: reduction,
+                ..Default::default()
+            }
+            .scale_cooldown(10.)
+        };
+        assert_eq!(cooldown(0.25), 8.);
+        assert_eq!(cooldown(1.), 5.);
+        assert_eq!(cooldown(3.), 2.5);
+        assert!(cooldown(1000.) > 0.);
+    }
+
+    fn magic_boots(mods: Vec<Mod>) -> Item {
+        Item {
+            name: "Magic Boots".to_string(),
+            slot: Slot::Boots,
+            rarity: Rarity::Magic,
+            mods,
+            effects: vec![],
+            set: None,
+        }
+    }
+
+    #[test]
+    fn reroll_needs_a_mod_with_a_value() {
+        let item = magic_boots(vec![Mod {
+            kind: ModKind::DashNoManaCost,
+            value: 1.,
+        }]);
+        assert!(matches!(
+            item.can_craft(Craft::Reroll),
+            Err(CraftError::NothingToReroll)
+        ));
+        assert!(item.can_craft(Craft::Chaos).is_ok());
+    }
+
+    #[test]
+    fn reroll_skips_behaviour_mods() {
+        let mut item = magic_boots(vec![
+            Mod {
+                kind: ModKind::DashBecomesTeleport,
+                value: 1.,
+            },
+            Mod {
+                kind: ModKind::MovementSpeed,
+                value: 0.,
+            },
+        ]);
+        let table = LootTable::default();
+        let mut rng = thread_rng();
+        for _ in 0..20 {
+            item.craft(Craft::Reroll, &mut rng, &table).unwrap();
+            assert_eq!(item.mods[0].value, 1.);
+        }
+        assert!(item.mods[1].value > 0.);
     }
 }
@@ -0,0 +1,125 @@
+use bevy::{log, prelude::*};
+
+use crate::{
+    button::{register_my_button, ClickedButtonEvent, MyButton, MyButtonBundle},
+    cleanup::cleanup_system,
+    objective::LevelResult,
+    player::Player,
+    GameState, RobotoFont,
+};
+
+struct LevelCompleteMarker;
+
+#[derive(Default, Clone, Debug)]
+struct ClickedOk;
+
+fn setup(
+    mut commands: Commands,
+    font: Res<RobotoFont>,
+    player: Query<&Transform, With<Player>>,
+    result: Res<Option<LevelResult>>,
+) {
+    let mut tr = player.single().expect("single player").translation;
+    tr.z += 0.5;
+    let mut tr = Transform::from_translation(tr);
+    let mut ui_bundle = commands.spawn_bundle(UiCameraBundle::default());
+    let ui_cmds = ui_bundle // root node
+        .commands();
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+    let complete = Text::with_section(
+        "LEVEL COMPLETE".to_string(),
+        TextStyle {
+            font: font.0.clone(),
+            font_size: 96.,
+            color: Color::GREEN,
+        },
+        alignment,
+    );
+    ui_cmds
+        .spawn_bundle(Text2dBundle {
+            text: complete,
+            transform: tr,
+            ..Default::default()
+        })
+        .insert(LevelCompleteMarker);
+    let result = result.as_ref().expect("level result is recorded");
+    let summary = Text::with_section(
+        format!(
+            "Level {} in {:.1}s, treasures: {}, detected: {}",
+            result.level, result.time, result.treasures, result.detections
+        ),
+        TextStyle {
+            font: font.0.clone(),
+            font_size: 48.,
+            color: Color::WHITE,
+        },
+        alignment,
+    );
+    tr.translation.y -= 80.;
+    ui_cmds
+        .spawn_bundle(Text2dBundle {
+            text: summary,
+            transform: tr,
+            ..Default::default()
+        })
+        .insert(LevelCompleteMarker);
+    let ok = Text::with_section(
+        "OK".to_string(),
+        TextStyle {
+            font: font.0.clone(),
+            font_size: 96.,
+            color: Color::WHITE,
+        },
+        alignment,
+    );
+    tr.translation.y -= 100.;
+    ui_cmds
+        .spawn_bundle(Text2dBundle {
+            text: ok,
+            transform: tr,
+            ..Default::default()
+        })
+        .insert(LevelCompleteMarker)
+        .with_children(|cmds| {
+            cmds.spawn_bundle(MyButtonBundle {
+                button: MyButton {
+                    size: Vec2::new(320., 60.),
+                    id: ClickedOk,
+                },
+                transform: Transform::from_xyz(0., 0., 0.001),
+                ..Default::default()
+            });
+        });
+    log::debug!("built level complete");
+}
+
+fn clicked_ok(
+    mut event_reader: EventReader<ClickedButtonEvent<ClickedOk>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if event_reader.iter().next().is_some() {
+        log::debug!("moving back to menu");
+        state.pop().expect("cant move back from level complete");
+    }
+}
+
+pub struct LevelCompletePlugin;
+impl Plugin for LevelCompletePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::LevelComplete).with_system(setup.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::LevelComplete)
+                .with_system(clicked_ok.system().after("button_click")),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::LevelComplete)
+                .with_system(cleanup_system::<LevelCompleteMarker>.system()),
+        );
+        register_my_button::<ClickedOk>(app, GameState::LevelComplete);
+    }
+}
@@ -0,0 +1,108 @@
+use std::{fmt::Display, fs::File, io::BufReader, path::PathBuf};
+
+use bevy::{asset::FileAssetIo, log, prelude::*};
+use serde::Deserialize;
+
+use crate::{error_screen::ErrorMessage, progress::Progress};
+
+/// Relative to the assets folder, like the maps it lists.
+const MANIFEST: &str = "levels.json";
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "Easy"),
+            Difficulty::Normal => write!(f, "Normal"),
+            Difficulty::Hard => write!(f, "Hard"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LevelInfo {
+    pub id: u32,
+    pub name: String,
+    /// A tiled map in the assets folder.
+    pub map: String,
+    pub description: String,
+    pub difficulty: Difficulty,
+    /// Id of the level to complete first.
+    #[serde(default)]
+    pub requires: Option<u32>,
+}
+
+impl LevelInfo {
+    pub fn is_unlocked(&self, progress: &Progress) -> bool {
+        progress.is_legacy_unlocked(self.id)
+            || self.requires.map_or(true, |id| progress.is_completed(id))
+    }
+}
+
+/// Levels in the order they are shown in the menu.
+#[derive(Debug, Default)]
+pub struct Levels(pub Vec<LevelInfo>);
+
+impl Levels {
+    pub fn get(&self, id: u32) -> Option<&LevelInfo> {
+        self.0.iter().find(|level| level.id == id)
+    }
+}
+
+fn manifest_path() -> PathBuf {
+    FileAssetIo::get_root_path().join("assets").join(MANIFEST)
+}
+
+fn read_manifest() -> Result<Vec<LevelInfo>, String> {
+    let path = manifest_path();
+    let file = File::open(&path).map_err(|e| format!("cant open {}: {}", path.display(), e))?;
+    let levels: Vec<LevelInfo> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("{} is malformed: {}", path.display(), e))?;
+    for level in levels.iter() {
+        if levels.iter().filter(|other| other.id == level.id).count() > 1 {
+            return Err(format!(
+                "level id {} is used twice in {}",
+                level.id, MANIFEST
+            ));
+        }
+        if let Some(id) = level.requires {
+            if !levels.iter().any(|other| other.id == id) {
+                return Err(format!(
+                    "level {} requires level {} which is not in {}",
+                    level.id, id, MANIFEST
+                ));
+            }
+        }
+    }
+    Ok(levels)
+}
+
+fn load_manifest(mut levels: ResMut<Levels>, mut error: ResMut<Option<ErrorMessage>>) {
+    match read_manifest() {
+        Ok(loaded) => {
+            log::info!("{} levels in the manifest", loaded.len());
+            levels.0 = loaded;
+        }
+        Err(e) => {
+            log::error!("cant load levels: {}", e);
+            *error = Some(ErrorMessage {
+                title: "Could not load the levels".to_string(),
+                details: e,
+            });
+        }
+    }
+}
+
+pub struct LevelsPlugin;
+impl Plugin for LevelsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Levels>()
+            .add_startup_system(load_manifest.system());
+    }
+}
@@ -29,7 +29,7 @@ fn spawn_smoke(
 ) {
     query.for_each(|entity| {
         let mut v_pos = vec![[0., 0.]];
-        let radius = SHADER_SIZE * (1. + stats.light_radius);
+        let radius = stats.scale_light_radius(SHADER_SIZE);
         // x =
         let origin = Vec2::new(radius, 0.);
         let mut indices = vec![];
@@ -53,7 +53,7 @@ fn spawn_smoke(
         mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
         mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uv);
         commands.entity(entity).with_children(|ec| {
-            let light_size = Vec2::splat(LIGHT_RADIUS * (1. + stats.light_radius));
+            let light_size = Vec2::splat(stats.scale_light_radius(LIGHT_RADIUS));
             let light = Sprite::new(light_size / 2.);
             ec.spawn_bundle(SpriteBundle {
                 sprite: light,
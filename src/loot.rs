@@ -0,0 +1,418 @@
+use std::{collections::BTreeMap, env, fs::File, io::BufReader, path::PathBuf};
+
+use bevy::{asset::FileAssetIo, log, prelude::*};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+
+use crate::{
+    error_screen::ErrorMessage,
+    items::{ItemSet, ModKind, Rarity, Slot, KINDS, SLOTS},
+    main_menu_ui::SelectedLevel,
+    GameState,
+};
+
+/// Relative to the assets folder.
+const TABLES: &str = "loot_tables.json";
+/// Fixes the rewards of every level, `--seed <number>` takes priority over it.
+pub const SEED_ENV: &str = "RUSTYJAM_SEED";
+const SEED_FLAG: &str = "--seed";
+/// As many cards as fit on the reward screen.
+pub const MAX_DROPS: usize = 3;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DropWeight {
+    /// Items offered by one treasure.
+    pub count: usize,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RarityWeight {
+    pub rarity: Rarity,
+    /// Mods rolled on an item of this rarity, unique rolls fall back to a rare with this many
+    /// when no unique fits the slots.
+    pub mods: usize,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModWeight {
+    pub kind: ModKind,
+    pub weight: u32,
+}
+
+/// What a treasure can give, every list is picked from by weight.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LootTable {
+    pub drops: Vec<DropWeight>,
+    pub rarities: Vec<RarityWeight>,
+    /// Slots are equally likely.
+    pub slots: Vec<Slot>,
+    pub mods: Vec<ModWeight>,
+    /// Sets a rolled item can be a piece of, uniques are never set pieces.
+    #[serde(default)]
+    pub sets: Vec<ItemSet>,
+    /// From 0 to 1.
+    #[serde(default)]
+    pub set_chance: f32,
+}
+
+impl LootTable {
+    fn check(&self) -> Result<(), String> {
+        if self.drops.iter().map(|d| d.weight).sum::<u32>() == 0 {
+            return Err("no drop counts with a weight".to_string());
+        }
+        if let Some(drop) = self
+            .drops
+            .iter()
+            .find(|d| d.count == 0 || d.count > MAX_DROPS)
+        {
+            return Err(format!(
+                "drop count {} is not between 1 and {}",
+                drop.count, MAX_DROPS
+            ));
+        }
+        if self.rarities.iter().map(|r| r.weight).sum::<u32>() == 0 {
+            return Err("no rarities with a weight".to_string());
+        }
+        if self.slots.is_empty() {
+            return Err("no slots".to_string());
+        }
+        if !(0. ..=1.).contains(&self.set_chance) {
+            return Err(format!(
+                "set chance {} is not between 0 and 1",
+                self.set_chance
+            ));
+        }
+        for a_mod in self.mods.iter() {
+            if self.mods.iter().filter(|m| m.kind == a_mod.kind).count() > 1 {
+                return Err(format!("mod {:?} is in the pool twice", a_mod.kind));
+            }
+        }
+        let pool = self.mods.iter().filter(|m| m.weight > 0).count();
+        if let Some(rarity) = self.rarities.iter().find(|r| r.mods == 0 || r.mods > pool) {
+            return Err(format!(
+                "{:?} items roll {} mods, the pool has {}",
+                rarity.rarity, rarity.mods, pool
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Odds the game shipped with before the tables were moved to the assets.
+impl Default for LootTable {
+    fn default() -> Self {
+        LootTable {
+            drops: vec![DropWeight {
+                count: 3,
+                weight: 1,
+            }],
+            rarities: vec![
+                RarityWeight {
+                    rarity: Rarity::Common,
+                    mods: 1,
+                    weight: 60,
+                },
+                RarityWeight {
+                    rarity: Rarity::Magic,
+                    mods: 2,
+                    weight: 30,
+                },
+                RarityWeight {
+                    rarity: Rarity::Rare,
+                    mods: 4,
+                    weight: 10,
+                },
+            ],
+            slots: SLOTS.to_vec(),
+            mods: KINDS
+                .iter()
+                .map(|kind| ModWeight {
+                    kind: *kind,
+                    weight: 1,
+                })
+                .collect(),
+            sets: vec![],
+            set_chance: 0.,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LootTables {
+    default: LootTable,
+    /// By level id, levels not listed use the default table.
+    #[serde(default)]
+    levels: BTreeMap<u32, LootTable>,
+}
+
+impl LootTables {
+    pub fn for_level(&self, level: u32) -> &LootTable {
+        self.levels.get(&level).unwrap_or(&self.default)
+    }
+
+    /// Crafting is not tied to a level, it rolls from the default pool.
+    pub fn crafting_table(&self) -> &LootTable {
+        &self.default
+    }
+}
+
+/// Every reward is rolled from this, one seed always gives the same rewards in the same order.
+/// It starts over for each level, so replaying a level with the same seed gives the same rewards.
+/// `StdRng` may change its algorithm between rand versions, ChaCha8 keeps old seeds working.
+pub struct LootRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl LootRng {
+    pub fn from_seed(seed: u64) -> Self {
+        LootRng {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Rewinds to the rolls of `level`, different levels do not share them.
+    pub fn start_level(&mut self, level: u32) {
+        self.rng = ChaCha8Rng::seed_from_u64(self.seed ^ u64::from(level));
+    }
+
+    pub fn rng(&mut self) -> &mut ChaCha8Rng {
+        &mut self.rng
+    }
+}
+
+impl Default for LootRng {
+    fn default() -> Self {
+        let seed = seed_from_args().unwrap_or_else(|| thread_rng().gen());
+        log::info!("loot seed is {}", seed);
+        LootRng::from_seed(seed)
+    }
+}
+
+/// `--seed`, then `RUSTYJAM_SEED`.
+fn seed_from_args() -> Option<u64> {
+    let mut args = env::args().skip(1);
+    let mut seed = None;
+    while let Some(arg) = args.next() {
+        if arg == SEED_FLAG {
+            seed = args.next();
+        } else if let Some(value) = arg.strip_prefix("--seed=") {
+            seed = Some(value.to_string());
+        }
+    }
+    let seed = seed.or_else(|| env::var(SEED_ENV).ok())?;
+    match seed.parse() {
+        Ok(seed) => Some(seed),
+        Err(e) => {
+            log::warn!("ignoring seed {}: {}", seed, e);
+            None
+        }
+    }
+}
+
+fn tables_path() -> PathBuf {
+    FileAssetIo::get_root_path().join("assets").join(TABLES)
+}
+
+fn read_tables() -> Result<LootTables, String> {
+    let path = tables_path();
+    let file = File::open(&path).map_err(|e| format!("cant open {}: {}", path.display(), e))?;
+    let tables: LootTables = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("{} is malformed: {}", path.display(), e))?;
+    tables
+        .default
+        .check()
+        .map_err(|e| format!("default table in {}: {}", TABLES, e))?;
+    for (level, table) in tables.levels.iter() {
+        table
+            .check()
+            .map_err(|e| format!("level {} table in {}: {}", level, TABLES, e))?;
+    }
+    Ok(tables)
+}
+
+fn reseed_for_level(mut loot: ResMut<LootRng>, level: Res<SelectedLevel>) {
+    log::debug!("loot seed {} for level {}", loot.seed(), level.0);
+    loot.start_level(level.0);
+}
+
+fn load_tables(mut tables: ResMut<LootTables>, mut error: ResMut<Option<ErrorMessage>>) {
+    match read_tables() {
+        Ok(loaded) => {
+            log::info!("loot tables for {} levels", loaded.levels.len());
+            *tables = loaded;
+        }
+        Err(e) => {
+            // the built in odds still work, rewards are not worth blocking the game for
+            log::error!("cant load loot tables: {}", e);
+            *error = Some(ErrorMessage {
+                title: "Could not load the loot tables".to_string(),
+                details: e,
+            });
+        }
+    }
+}
+
+pub struct LootPlugin;
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<LootTables>()
+            .init_resource::<LootRng>()
+            .add_startup_system(load_tables.system())
+            .add_system_set(
+                SystemSet::on_enter(GameState::LoadingLevel).with_system(reseed_for_level.system()),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::{
+        items::{self, Item},
+        uniques::Uniques,
+    };
+
+    fn table() -> LootTable {
+        LootTable {
+            drops: vec![
+                DropWeight {
+                    count: 2,
+                    weight: 1,
+                },
+                DropWeight {
+                    count: 3,
+                    weight: 1,
+                },
+            ],
+            rarities: vec![
+                RarityWeight {
+                    rarity: Rarity::Common,
+                    mods: 1,
+                    weight: 2,
+                },
+                RarityWeight {
+                    rarity: Rarity::Magic,
+                    mods: 2,
+                    weight: 2,
+                },
+                RarityWeight {
+                    rarity: Rarity::Rare,
+                    mods: 3,
+                    weight: 1,
+                },
+            ],
+            slots: SLOTS.to_vec(),
+            mods: vec![
+                ModWeight {
+                    kind: ModKind::MovementSpeed,
+                    weight: 3,
+                },
+                ModWeight {
+                    kind: ModKind::ManaRegen,
+                    weight: 2,
+                },
+                ModWeight {
+                    kind: ModKind::Duration,
+                    weight: 1,
+                },
+                ModWeight {
+                    kind: ModKind::DashNoManaCost,
+                    weight: 1,
+                },
+            ],
+            sets: vec![ItemSet::Nightstalker],
+            set_chance: 0.5,
+        }
+    }
+
+    /// One line per item, values are rounded as the test is about which rolls happen in what order.
+    fn summary(items: &[Item]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| {
+                let mods = item
+                    .mods
+                    .iter()
+                    .map(|a_mod| format!("{:?} {:.3}", a_mod.kind, a_mod.value))
+                    .join(", ");
+                format!("{} ({:?}): {}", item.name, item.rarity, mods)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seed_gives_the_same_items() {
+        let table = table();
+        table.check().unwrap();
+        let mut loot = LootRng::from_seed(1234);
+        let rolls = (0..3)
+            .map(|_| summary(&items::generate(loot.rng(), &table, &Uniques::default())))
+            .collect::<Vec<_>>();
+        // changes here mean old seeds no longer give the same rewards
+        assert_eq!(
+            rolls,
+            vec![
+                vec![
+                    "Mask (Common): MovementSpeed 0.081",
+                    "Nightstalker Lockpick (Magic): ManaRegen 2.000, MovementSpeed 0.096",
+                    "Nightstalker Mask (Common): ManaRegen 2.000",
+                ],
+                vec![
+                    "Nightstalker Boots (Common): MovementSpeed 0.071",
+                    "Magic Boots (Magic): Duration 0.145, DashNoManaCost 1.000",
+                ],
+                vec![
+                    "Nightstalker Lockpick (Magic): DashNoManaCost 1.000, Duration 0.145",
+                    "Magic Boots (Magic): MovementSpeed 0.097, DashNoManaCost 1.000",
+                    "Magic Mask (Magic): MovementSpeed 0.188, DashNoManaCost 1.000",
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn sets_without_a_chance_change_nothing() {
+        let with_sets = LootTable {
+            set_chance: 0.,
+            ..table()
+        };
+        let without_sets = LootTable {
+            sets: vec![],
+            ..with_sets.clone()
+        };
+        let mut a = LootRng::from_seed(99);
+        let mut b = LootRng::from_seed(99);
+        for _ in 0..10 {
+            let uniques = Uniques::default();
+            assert_eq!(
+                summary(&items::generate(a.rng(), &with_sets, &uniques)),
+                summary(&items::generate(b.rng(), &without_sets, &uniques))
+            );
+        }
+    }
+    #[test]
+    fn replaying_a_level_gives_the_same_rewards() {
+        let table = table();
+        let uniques = Uniques::default();
+        let mut loot = LootRng::from_seed(1234);
+        let mut play = |level| {
+            loot.start_level(level);
+            summary(&items::generate(loot.rng(), &table, &uniques))
+        };
+        let first = play(1);
+        assert_eq!(play(1), first);
+        assert_ne!(play(2), first);
+        assert_eq!(play(1), first);
+    }
+}
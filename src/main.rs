@@ -1,40 +1,72 @@
+mod autosave;
+mod boundaries;
 mod button;
 mod camera_enemy;
 mod castbar;
 mod cleanup;
+mod effects;
+mod emp;
+mod error_screen;
 mod game_over;
+mod geometry;
+mod guard;
 mod inventory;
 mod items;
+mod level_complete;
+mod levels;
 mod light_radius;
+mod loot;
 mod main_menu_ui;
+mod mana;
 mod map;
+mod map_validation;
 mod movement;
+mod objective;
+mod pathfinding;
 mod perlin;
 mod player;
+mod profiles;
+mod profiles_screen;
+mod progress;
 mod reward;
+mod save;
 mod skills;
 mod smoke_bomb;
 mod stats_screen;
 mod treasure;
 mod ui;
+mod uniques;
 
 use bevy::{diagnostic::FrameTimeDiagnosticsPlugin, prelude::*};
+use autosave::AutosavePlugin;
 use bevy_ecs_tilemap::prelude::*;
 use button::MyButtonPlugin;
 use camera_enemy::EnemyCameraPlugin;
 use castbar::CastbarPlugin;
+use effects::EffectsPlugin;
+use emp::EmpPlugin;
+use error_screen::ErrorScreenPlugin;
 use game_over::GameoverPlugin;
+use guard::GuardPlugin;
 use inventory::InventoryScreenPlugin;
+use level_complete::LevelCompletePlugin;
+use levels::LevelsPlugin;
 use light_radius::LightRadiusPlugin;
+use loot::LootPlugin;
 use main_menu_ui::MainMenuUiPlugin;
+use mana::ManaPlugin;
 use map::MapPlugin;
+use objective::ObjectivePlugin;
 use perlin::PerlinPlugin;
+use profiles_screen::ProfilesScreenPlugin;
 use reward::RewardPlugin;
+use save::SavePlugin;
 use skills::SkillsUiPlugin;
 use smoke_bomb::SmokeBombPlugin;
 use stats_screen::StatsScreenPlugin;
 use treasure::TreasurePlugin;
 use ui::UiPlugin;
+use uniques::UniquesPlugin;
 
 use crate::{movement::MovementPlugin, player::PlayerPlugin};
 
@@ -47,6 +79,9 @@ pub enum GameState {
     Level,
     ChoosingTreasure,
     GameOver,
+    LevelComplete,
+    ErrorScreen,
+    ProfilesScreen,
 }
 
 pub const WIDTH: f32 = 1920. * 0.9;
@@ -71,9 +106,12 @@ fn main() {
         .add_plugin(MapPlugin)
         .add_plugin(PerlinPlugin)
         .add_plugin(EnemyCameraPlugin)
+        .add_plugin(GuardPlugin)
         .add_plugin(FrameTimeDiagnosticsPlugin)
         .add_plugin(UiPlugin)
         .add_plugin(SmokeBombPlugin)
+        .add_plugin(EmpPlugin)
+        .add_plugin(ManaPlugin)
         .add_plugin(LightRadiusPlugin)
         .add_plugin(MainMenuUiPlugin)
         .add_plugin(MyButtonPlugin)
@@ -84,6 +122,16 @@ fn main() {
         .add_plugin(SkillsUiPlugin)
         .add_plugin(CastbarPlugin)
         .add_plugin(GameoverPlugin)
+        .add_plugin(ObjectivePlugin)
+        .add_plugin(LevelCompletePlugin)
+        .add_plugin(ErrorScreenPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(LevelsPlugin)
+        .add_plugin(LootPlugin)
+        .add_plugin(UniquesPlugin)
+        .add_plugin(EffectsPlugin)
+        .add_plugin(AutosavePlugin)
+        .add_plugin(ProfilesScreenPlugin)
         .add_startup_system(setup.system())
         .init_resource::<RobotoFont>()
         .run();
@@ -1,13 +1,15 @@
-use std::{fs::File, io::Write};
-
 use bevy::{log, prelude::*, window::WindowResized};
-use itertools::Itertools;
 
 use crate::{
+    autosave::{Autosave, SaveStatus},
     button::{register_my_button, ClickedButtonEvent, MyButton, MyButtonBundle},
     cleanup::cleanup_system,
+    effects::ActiveEffects,
     inventory::ViewInvSlot,
-    items::{Item, PlayerItems, PlayerStatsMods, Slot},
+    items::{PlayerItems, PlayerStatsMods, Slot, SET_PIECES},
+    levels::{LevelInfo, Levels},
+    profiles::Profiles,
+    progress::Progress,
     GameState, MainCamera, RobotoFont, HEIGHT, WIDTH,
 };
 
@@ -26,6 +28,15 @@ pub struct SelectedLevel(pub u32);
 #[derive(Debug, Clone, Copy, Default)]
 pub struct ClickedSave;
 
+#[derive(Debug, Clone, Copy, Default)]
+struct ClickedProfile;
+
+struct ProfileLabel;
+
+struct SaveStatusLabel;
+
+struct LevelInfoLabel(u32);
+
 struct MainMenuMarker;
 
 struct CurrentItemsView(Entity);
@@ -34,7 +45,8 @@ pub fn light_text_color() -> Color {
     Color::rgb_u8(255, 252, 236)
 }
 
-fn draw_slot(cmds: &mut ChildBuilder, item: &Item, font_handle: &RobotoFont) {
+fn draw_slot(cmds: &mut ChildBuilder, items: &PlayerItems, slot: Slot, font_handle: &RobotoFont) {
+    let item = items.slot_items(slot).equipped();
     let y = match item.slot {
         crate::items::Slot::Head => 220.,
         crate::items::Slot::Cloak => 55.,
@@ -70,12 +82,7 @@ fn draw_slot(cmds: &mut ChildBuilder, item: &Item, font_handle: &RobotoFont) {
             ..Default::default()
         });
 
-        let text = item
-            .mods
-            .iter()
-            .map(|item_mod| item_mod.to_string())
-            .collect_vec()
-            .join("\n");
+        let text = item.description();
         let item_mods = Text::with_section(
             text,
             TextStyle {
@@ -93,6 +100,153 @@ fn draw_slot(cmds: &mut ChildBuilder, item: &Item, font_handle: &RobotoFont) {
             transform: Transform::from_xyz(x, -20., 0.001),
             ..Default::default()
         });
+
+        if let Some(set) = item.set {
+            let pieces = items
+                .equipped_sets()
+                .into_iter()
+                .find(|(worn, _)| *worn == set)
+                .map_or(0, |(_, pieces)| pieces);
+            // gold once the set grants anything
+            let is_active = set.bonuses().iter().any(|bonus| pieces >= bonus.pieces);
+            let set_text = Text::with_section(
+                format!("{} {}/{}", set.name(), pieces, SET_PIECES),
+                TextStyle {
+                    font: font_handle.0.clone(),
+                    font_size: 15.0,
+                    color: if is_active { Color::GOLD } else { color },
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            );
+            cmds.spawn_bundle(Text2dBundle {
+                text: set_text,
+                transform: Transform::from_xyz(x, -64., 0.001),
+                ..Default::default()
+            });
+        }
+    });
+}
+
+fn level_info(info: &LevelInfo, levels: &Levels, progress: &Progress) -> String {
+    if !info.is_unlocked(progress) {
+        let required = info.requires.and_then(|id| levels.get(id));
+        return format!(
+            "Locked, complete {} first",
+            required.map_or("the previous level", |level| level.name.as_str())
+        );
+    }
+    match progress.level(info.id) {
+        Some(p) if p.completed => format!(
+            "Best time {:.1}s, treasures {}, detected {} times",
+            p.best_time.unwrap_or_default(),
+            p.most_treasures,
+            p.fewest_detections.unwrap_or_default()
+        ),
+        _ => "Not completed".to_string(),
+    }
+}
+
+fn level_info_text(
+    info: &LevelInfo,
+    levels: &Levels,
+    progress: &Progress,
+    font: Handle<Font>,
+) -> Text {
+    let color = if info.is_unlocked(progress) {
+        light_text_color()
+    } else {
+        Color::GRAY
+    };
+    Text::with_section(
+        level_info(info, levels, progress),
+        TextStyle {
+            font,
+            font_size: 24.0,
+            color,
+        },
+        TextAlignment {
+            vertical: VerticalAlign::Center,
+            horizontal: HorizontalAlign::Left,
+        },
+    )
+}
+
+fn draw_level(
+    cmds: &mut ChildBuilder,
+    index: usize,
+    levels: &Levels,
+    progress: &Progress,
+    font_handle: &Handle<Font>,
+) {
+    let info = &levels.0[index];
+    let color = light_text_color();
+    let left = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Left,
+    };
+    cmds.spawn_bundle(MyButtonBundle {
+        button: MyButton {
+            size: Vec2::new(940., 156.),
+            id: ClickedLevel(info.id),
+        },
+        transform: Transform::from_xyz(330., 220. - index as f32 * 175., 0.001),
+        ..Default::default()
+    })
+    .with_children(|cmds| {
+        let name = Text::with_section(
+            info.name.clone(),
+            TextStyle {
+                font: font_handle.clone(),
+                font_size: 50.0,
+                color,
+            },
+            left,
+        );
+        cmds.spawn_bundle(Text2dBundle {
+            text: name,
+            transform: Transform::from_xyz(-440., 40., 0.001),
+            ..Default::default()
+        });
+        let difficulty = Text::with_section(
+            info.difficulty.to_string(),
+            TextStyle {
+                font: font_handle.clone(),
+                font_size: 30.0,
+                color,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Right,
+            },
+        );
+        cmds.spawn_bundle(Text2dBundle {
+            text: difficulty,
+            transform: Transform::from_xyz(440., 40., 0.001),
+            ..Default::default()
+        });
+        let description = Text::with_section(
+            info.description.clone(),
+            TextStyle {
+                font: font_handle.clone(),
+                font_size: 20.0,
+                color,
+            },
+            left,
+        );
+        cmds.spawn_bundle(Text2dBundle {
+            text: description,
+            transform: Transform::from_xyz(-440., -5., 0.001),
+            ..Default::default()
+        });
+        cmds.spawn_bundle(Text2dBundle {
+            text: level_info_text(info, levels, progress, font_handle.clone()),
+            transform: Transform::from_xyz(-440., -45., 0.001),
+            ..Default::default()
+        })
+        .insert(LevelInfoLabel(info.id));
     });
 }
 
@@ -101,6 +255,9 @@ fn setup(
     asset_server: ResMut<AssetServer>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut camera: Query<&mut Transform, With<MainCamera>>,
+    profiles: Res<Profiles>,
+    progress: Res<Progress>,
+    levels: Res<Levels>,
 ) {
     let mut cam = camera.single_mut().unwrap();
     cam.translation = Vec3::new(0., 0., 999.);
@@ -160,55 +317,57 @@ fn setup(
                 });
             });
 
-            cmds.spawn_bundle(MyButtonBundle {
-                button: MyButton {
-                    size: Vec2::new(940., 156.),
-                    id: ClickedLevel(1),
+            let save_status = Text::with_section(
+                String::new(),
+                TextStyle {
+                    font: font_handle.clone(),
+                    font_size: 16.0,
+                    color: Color::rgb_u8(255, 252, 236),
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
                 },
-                transform: Transform::from_xyz(330., 220., 0.001),
+            );
+            cmds.spawn_bundle(Text2dBundle {
+                text: save_status,
+                transform: Transform::from_xyz(660., 310., 0.001),
                 ..Default::default()
             })
-            .with_children(|cmds| {
-                let level1 = Text::with_section(
-                    "Level 1".to_string(),
-                    TextStyle {
-                        font: font_handle.clone(),
-                        font_size: 50.0,
-                        color: Color::rgb_u8(255, 252, 236),
-                    },
-                    Default::default(),
-                );
-                cmds.spawn_bundle(Text2dBundle {
-                    text: level1,
-                    transform: Transform::from_xyz(-330., 20., 0.001),
-                    ..Default::default()
-                });
-            });
+            .insert(SaveStatusLabel);
 
             cmds.spawn_bundle(MyButtonBundle {
                 button: MyButton {
-                    size: Vec2::new(940., 156.),
-                    id: ClickedLevel(2),
+                    size: Vec2::new(260., 60.),
+                    id: ClickedProfile,
                 },
-                transform: Transform::from_xyz(330., 45., 0.001),
+                transform: Transform::from_xyz(430., 360., 0.001),
                 ..Default::default()
             })
             .with_children(|cmds| {
-                let level1 = Text::with_section(
-                    "Debug reward level".to_string(),
+                let profile = Text::with_section(
+                    format!("Profile: {}", profiles.current()),
                     TextStyle {
                         font: font_handle.clone(),
-                        font_size: 50.0,
+                        font_size: 24.0,
                         color: Color::rgb_u8(255, 252, 236),
                     },
-                    Default::default(),
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
                 );
                 cmds.spawn_bundle(Text2dBundle {
-                    text: level1,
-                    transform: Transform::from_xyz(-90., 20., 0.001),
+                    text: profile,
+                    transform: Transform::from_xyz(0., 0., 0.001),
                     ..Default::default()
-                });
+                })
+                .insert(ProfileLabel);
             });
+
+            for index in 0..levels.0.len() {
+                draw_level(cmds, index, &levels, &progress, &font_handle);
+            }
         });
 
     cmds.spawn()
@@ -279,10 +438,10 @@ fn dispatch_items(
         .insert(Transform::from_xyz(530., 0., 0.3))
         .insert(GlobalTransform::default())
         .with_children(|cmds| {
-            draw_slot(cmds, items.head.equipped(), &font);
-            draw_slot(cmds, items.cloak.equipped(), &font);
-            draw_slot(cmds, items.lockpick.equipped(), &font);
-            draw_slot(cmds, items.boots.equipped(), &font);
+            draw_slot(cmds, &items, Slot::Head, &font);
+            draw_slot(cmds, &items, Slot::Cloak, &font);
+            draw_slot(cmds, &items, Slot::Lockpick, &font);
+            draw_slot(cmds, &items, Slot::Boots, &font);
         })
         .id();
 
@@ -301,6 +460,43 @@ fn clicked_stats(
     }
 }
 
+fn clicked_profile(
+    mut event_reader: EventReader<ClickedButtonEvent<ClickedProfile>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if event_reader.iter().next().is_some() {
+        log::debug!("moving to profiles screen");
+        state
+            .push(GameState::ProfilesScreen)
+            .expect("cant move to profiles screen");
+    }
+}
+
+// the menu is only paused while profiles are edited, so the label is not respawned
+fn update_profile_label(profiles: Res<Profiles>, labels: Query<&mut Text, With<ProfileLabel>>) {
+    if profiles.is_changed() {
+        labels.for_each_mut(|mut text| {
+            text.sections[0].value = format!("Profile: {}", profiles.current());
+        });
+    }
+}
+
+// progress changes while the menu is paused under the profiles screen
+fn update_level_info(
+    progress: Res<Progress>,
+    levels: Res<Levels>,
+    labels: Query<(&mut Text, &LevelInfoLabel)>,
+) {
+    if progress.is_changed() {
+        labels.for_each_mut(|(mut text, LevelInfoLabel(id))| {
+            if let Some(info) = levels.get(*id) {
+                let font = text.sections[0].style.font.clone();
+                *text = level_info_text(info, &levels, &progress, font);
+            }
+        });
+    }
+}
+
 fn clicked_slot(
     mut event_reader: EventReader<ClickedButtonEvent<ClickedSlot>>,
     mut state: ResMut<State<GameState>>,
@@ -321,12 +517,23 @@ fn clicked_level(
     mut state: ResMut<State<GameState>>,
     items: Res<PlayerItems>,
     mut stats: ResMut<PlayerStatsMods>,
+    mut effects: ResMut<ActiveEffects>,
     mut sel_level: ResMut<SelectedLevel>,
+    progress: Res<Progress>,
+    levels: Res<Levels>,
 ) {
     for ClickedButtonEvent(ClickedLevel(level)) in event_reader.iter() {
+        let is_unlocked = levels
+            .get(*level)
+            .map_or(false, |info| info.is_unlocked(&progress));
+        if !is_unlocked {
+            log::debug!("level {} is locked", level);
+            continue;
+        }
         log::debug!("moving to playing");
         sel_level.0 = *level;
         *stats = items.stats();
+        *effects = ActiveEffects::from_items(&items);
         state
             .set(GameState::LoadingLevel)
             .expect("cant move to playing");
@@ -335,16 +542,34 @@ fn clicked_level(
 
 fn clicked_save(
     mut event_reader: EventReader<ClickedButtonEvent<ClickedSave>>,
-    items: Res<PlayerItems>,
+    mut autosave: ResMut<Autosave>,
 ) {
-    for _ in event_reader.iter() {
-        let json =
-            serde_json::to_string_pretty(&items as &PlayerItems).expect("cant serialize items");
-        let mut file = File::create("save.json").expect("cant create file to save");
-        file.write_all(json.as_bytes()).expect("cant write a save");
+    if event_reader.iter().next().is_some() {
+        autosave.save_soon();
     }
 }
 
+fn update_save_status(autosave: Res<Autosave>, labels: Query<&mut Text, With<SaveStatusLabel>>) {
+    let status = match autosave.status() {
+        SaveStatus::Never => "Not saved yet".to_string(),
+        SaveStatus::Pending => "Unsaved changes".to_string(),
+        SaveStatus::Saving => "Saving...".to_string(),
+        SaveStatus::Failed => "Last save failed".to_string(),
+        SaveStatus::Disabled => "Saving is off".to_string(),
+        SaveStatus::Saved(time) => match time.elapsed().as_secs() {
+            0..=4 => "Saved just now".to_string(),
+            secs @ 5..=59 => format!("Saved {}s ago", secs),
+            secs => format!("Saved {} min ago", secs / 60),
+        },
+    };
+    labels.for_each_mut(|mut text| {
+        // setting the same value would still relayout the text every frame
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    });
+}
+
 pub fn change_camera_scale_from_resize(
     mut query: Query<&mut Transform, With<MainCamera>>,
     mut events: EventReader<WindowResized>,
@@ -379,6 +604,10 @@ impl Plugin for MainMenuUiPlugin {
                     .with_system(clicked_stats.system().after("button_click"))
                     .with_system(clicked_level.system().after("button_click"))
                     .with_system(clicked_save.system().after("button_click"))
+                    .with_system(update_save_status.system())
+                    .with_system(update_level_info.system())
+                    .with_system(clicked_profile.system().after("button_click"))
+                    .with_system(update_profile_label.system())
                     .with_system(dispatch_items.system().label("dispatch_inventory"))
                     .with_system(change_camera_scale_from_resize.system()),
             );
@@ -386,5 +615,6 @@ impl Plugin for MainMenuUiPlugin {
         register_my_button::<ClickedSlot>(app, GameState::MainMenu);
         register_my_button::<ClickedLevel>(app, GameState::MainMenu);
         register_my_button::<ClickedSave>(app, GameState::MainMenu);
+        register_my_button::<ClickedProfile>(app, GameState::MainMenu);
     }
 }
@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+
+use crate::{items::PlayerStatsMods, GameState};
+
+pub const BASE_MANA: f32 = 100.;
+// per second
+pub const BASE_MANA_REGEN: f32 = 4.;
+
+#[derive(Debug, Default)]
+pub struct Mana {
+    pub current: f32,
+    pub max: f32,
+    // per second
+    pub regen: f32,
+}
+
+impl Mana {
+    pub fn from_stats(stats: &PlayerStatsMods) -> Self {
+        let max = BASE_MANA + stats.total_mana;
+        Mana {
+            current: max,
+            max,
+            regen: BASE_MANA_REGEN + stats.mana_regen,
+        }
+    }
+
+    pub fn can_afford(&self, cost: f32) -> bool {
+        self.current >= cost
+    }
+
+    pub fn spend(&mut self, cost: f32) {
+        self.current = (self.current - cost).max(0.);
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0. {
+            self.current / self.max
+        } else {
+            0.
+        }
+    }
+}
+
+fn reset_mana(mut mana: ResMut<Mana>, stats: Res<PlayerStatsMods>) {
+    *mana = Mana::from_stats(&stats);
+}
+
+fn regen_mana(mut mana: ResMut<Mana>, time: Res<Time>) {
+    // don't touch the resource when full, so the bar only updates on change
+    if mana.current < mana.max {
+        let regen = mana.regen * time.delta_seconds();
+        mana.current = (mana.current + regen).min(mana.max);
+    }
+}
+
+pub struct ManaPlugin;
+impl Plugin for ManaPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Mana>()
+            .add_system_set(SystemSet::on_enter(GameState::Level).with_system(reset_mana.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Level).with_system(regen_mana.system()),
+            );
+    }
+}
@@ -1,53 +1,76 @@
-use bevy::{
-    log,
-    prelude::*,
-    render::texture::FilterMode,
-    sprite::collide_aabb::{collide, Collision},
-};
+use bevy::{asset::LoadState, log, prelude::*, render::texture::FilterMode};
 use bevy_ecs_tilemap::prelude::*;
-use tiled::PropertyValue;
+use itertools::Itertools;
 
 use crate::{
-    camera_enemy::CameraSpawn,
+    boundaries::Boundaries,
+    camera_enemy::{CameraSpawn, CameraSweep},
+    error_screen::ErrorMessage,
+    guard::GuardSpawn,
     items::PlayerItems,
+    levels::Levels,
     main_menu_ui::SelectedLevel,
-    player::{LevelMarker, PLAYER_SIZE},
+    map_validation::{validate, GUARD_FOV, GUARD_RADIUS, GUARD_SPEED},
+    objective::ExitZone,
+    pathfinding::NavGrid,
+    player::LevelMarker,
     treasure::TreasureSpawn,
     GameState, MainCamera,
 };
 
 // pub struct TiledMapHandle(Handle<TiledMap>);
 
-#[derive(Default)]
-pub struct Boundaries(Vec<(Vec3, Vec2)>);
-
 #[derive(Default)]
 pub struct SpawnPoint(pub Option<Vec2>);
 
 #[derive(Default)]
 struct CurrentLevelHandle(Handle<TiledMap>);
 
-impl Boundaries {
-    pub fn collide(&self, player_pos: Vec3) -> Option<Collision> {
-        let player_size = Vec2::splat(PLAYER_SIZE);
-        self.0
-            .iter()
-            .find_map(|(pos, size)| collide(*pos, *size, player_pos, player_size))
-    }
-}
-
+#[allow(clippy::too_many_arguments)]
 fn load(
     asset_server: Res<AssetServer>,
     mut current_level: ResMut<CurrentLevelHandle>,
     sel_level: Res<SelectedLevel>,
+    levels: Res<Levels>,
+    mut bounds: ResMut<Boundaries>,
+    mut camera_spawns: ResMut<Vec<CameraSpawn>>,
+    mut guard_spawns: ResMut<Vec<GuardSpawn>>,
+    mut error: ResMut<Option<ErrorMessage>>,
+    mut state: ResMut<State<GameState>>,
 ) {
-    let path = format!("level{}.tmx", sel_level.0);
-    log::info!("loading {}", path);
-    let handle: Handle<TiledMap> = asset_server.load(path.as_str());
+    // leftovers from the previous level
+    *bounds = Boundaries::default();
+    camera_spawns.clear();
+    guard_spawns.clear();
+
+    let info = if let Some(info) = levels.get(sel_level.0) {
+        info
+    } else {
+        log::error!("level {} is not in the manifest", sel_level.0);
+        current_level.0 = Handle::default();
+        *error = Some(ErrorMessage {
+            title: "Could not load the level".to_string(),
+            details: format!("There is no level {} in the level list", sel_level.0),
+        });
+        state
+            .set(GameState::ErrorScreen)
+            .expect("cant move to error screen");
+        return;
+    };
+    log::info!("loading {} from {}", info.name, info.map);
+    let handle: Handle<TiledMap> = asset_server.load(info.map.as_str());
     current_level.0 = handle;
 }
 
-fn spawn_map(mut commands: Commands, current_level: Res<CurrentLevelHandle>) {
+fn spawn_map(
+    mut commands: Commands,
+    current_level: Res<CurrentLevelHandle>,
+    map_assets: Res<Assets<TiledMap>>,
+) {
+    // leaving without a map for the error screen
+    if map_assets.get(&current_level.0).is_none() {
+        return;
+    }
     let map_entity = commands.spawn().id();
 
     let transform = Transform::from_xyz(0.0, 0.0, 0.0);
@@ -69,87 +92,103 @@ fn load_boundaries(
     mut bounds: ResMut<Boundaries>,
     mut spawn: ResMut<SpawnPoint>,
     mut camera_spawns: ResMut<Vec<CameraSpawn>>,
+    mut guard_spawns: ResMut<Vec<GuardSpawn>>,
     map_assets: ResMut<Assets<TiledMap>>,
     mut state: ResMut<State<GameState>>,
     mut camera: Query<&mut Transform, With<MainCamera>>,
     current_level: Res<CurrentLevelHandle>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Levels>,
+    sel_level: Res<SelectedLevel>,
+    mut error: ResMut<Option<ErrorMessage>>,
 ) {
     let handle: Handle<TiledMap> = current_level.0.clone();
+    if asset_server.get_load_state(&handle) == LoadState::Failed {
+        let map = levels.get(sel_level.0).map_or("", |info| info.map.as_str());
+        log::error!("cant load map {}", map);
+        *error = Some(ErrorMessage {
+            title: "Could not load the level".to_string(),
+            details: format!("The map {} is missing or malformed, see the log", map),
+        });
+        state
+            .set(GameState::ErrorScreen)
+            .expect("cant move to error screen");
+        return;
+    }
     let map = if let Some(x) = map_assets.get(handle) {
         x
     } else {
         return;
     };
-    let map_y = (map.map.height * map.map.tile_height) as f32;
-    let mut spawn_x = 0.;
-    let mut spawn_y = 0.;
-    for group in map.map.object_groups.iter() {
-        match group.name.as_str() {
-            "Obstacles" => {
-                for obj in group.objects.iter() {
-                    bounds.0.push((
-                        Vec3::new(
-                            obj.x + obj.width / 2.,
-                            (map_y - obj.y) - obj.height / 2.,
-                            0.6,
-                        ),
-                        Vec2::new(obj.width, obj.height),
-                    ));
-                }
-            }
-            "Spawn" => {
-                let spawn_obj = group.objects.first().expect("at least one spawn point");
-                spawn_x = spawn_obj.x;
-                spawn_y = map_y - spawn_obj.y;
-                spawn.0 = Some(Vec2::new(spawn_x, spawn_y));
+    let layout = match validate(&map.map) {
+        Ok(layout) => layout,
+        Err(problems) => {
+            let map = levels.get(sel_level.0).map_or("", |info| info.map.as_str());
+            for problem in problems.iter() {
+                log::error!("{}: {}", map, problem);
             }
-            "Treasure" => {
-                for obj in group.objects.iter() {
-                    commands
-                        .spawn()
-                        .insert(TreasureSpawn)
-                        .insert(Transform::from_xyz(obj.x, map_y - obj.y, 0.7));
-                }
-            }
-            "Cameras" => {
-                for spawn_obj in group.objects.iter() {
-                    let x = spawn_obj.x;
-                    let y = map_y - spawn_obj.y;
-                    let props = &spawn_obj.properties;
-                    let radius = if let Some(PropertyValue::FloatValue(x)) = props.get("radius") {
-                        *x
-                    } else {
-                        panic!("no start_angle")
-                    };
-                    let start_angle =
-                        if let Some(PropertyValue::FloatValue(x)) = props.get("start_angle") {
-                            x.to_radians()
-                        } else {
-                            panic!("no start_angle")
-                        };
-                    let end_angle =
-                        if let Some(PropertyValue::FloatValue(x)) = props.get("end_angle") {
-                            x.to_radians()
-                        } else {
-                            panic!("no start_angle")
-                        };
-                    camera_spawns.push(CameraSpawn {
-                        x,
-                        y,
-                        radius,
-                        start_angle,
-                        end_angle,
-                    });
-                }
-            }
-            _ => {
-                log::error!("Unknown object layer: {}", group.name);
+            *error = Some(ErrorMessage {
+                title: format!("The map {} has problems", map),
+                details: problems.iter().map(ToString::to_string).join("\n"),
+            });
+            state
+                .set(GameState::ErrorScreen)
+                .expect("cant move to error screen");
+            return;
+        }
+    };
+    spawn.0 = Some(layout.spawn);
+    for pos in layout.treasures.iter() {
+        commands
+            .spawn()
+            .insert(TreasureSpawn)
+            .insert(Transform::from_translation(pos.extend(0.7)));
+    }
+    for (pos, size) in layout.exits.iter() {
+        commands
+            .spawn()
+            .insert(ExitZone { size: *size })
+            .insert(LevelMarker)
+            .insert(Transform::from_translation(pos.extend(0.1)));
+    }
+    camera_spawns.extend(layout.cameras.into_iter().map(|camera| CameraSpawn {
+        x: camera.pos.x,
+        y: camera.pos.y,
+        radius: camera.radius,
+        start_angle: camera.start_angle,
+        end_angle: camera.end_angle,
+        sweep: camera.sweep.map(|sweep| CameraSweep {
+            speed: sweep.speed,
+            range: sweep.range,
+            pause: sweep.pause,
+        }),
+    }));
+    guard_spawns.extend(layout.guards.into_iter().map(|guard| GuardSpawn {
+        waypoints: guard.waypoints,
+        looped: guard.looped,
+        speed: guard.speed.unwrap_or(GUARD_SPEED),
+        radius: guard.radius.unwrap_or(GUARD_RADIUS),
+        fov: guard.fov.unwrap_or_else(|| GUARD_FOV.to_radians()),
+    }));
+    *bounds = Boundaries::new(layout.obstacles);
+    for guard in guard_spawns.iter() {
+        for waypoint in guard.waypoints.iter() {
+            if bounds.is_blocked(*waypoint) {
+                log::warn!("guard waypoint {} is inside an obstacle", waypoint);
             }
         }
     }
+    let nav_grid = NavGrid::from_boundaries(
+        layout.width as usize,
+        layout.height as usize,
+        layout.tile_size,
+        &bounds,
+    );
+    commands.insert_resource(nav_grid);
+
     let mut camera_tr = camera.single_mut().expect("inexisting camera");
-    camera_tr.translation.x = spawn_x;
-    camera_tr.translation.y = spawn_y;
+    camera_tr.translation.x = layout.spawn.x;
+    camera_tr.translation.y = layout.spawn.y;
     // camera_tr.scale.x = 1. / 2.;
     // camera_tr.scale.y = 1. / 2.;
     state.set(GameState::Level).expect("cant set state");
@@ -200,6 +239,7 @@ impl Plugin for MapPlugin {
         app.init_resource::<CurrentLevelHandle>()
             .init_resource::<SpawnPoint>()
             .init_resource::<Boundaries>()
+            .init_resource::<NavGrid>()
             // .add_system(set_texture_filters_to_nearest.system())
             .add_system_set(
                 SystemSet::on_enter(GameState::LoadingLevel)
@@ -0,0 +1,543 @@
+use std::fmt::Display;
+
+use bevy::math::{Vec2, Vec3};
+use tiled::{Map, Object, ObjectShape, PropertyValue};
+
+#[derive(Debug, Clone, Copy)]
+pub enum PropertyType {
+    Bool,
+    Int,
+    Float,
+}
+
+impl Display for PropertyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropertyType::Bool => write!(f, "bool"),
+            PropertyType::Int => write!(f, "int"),
+            PropertyType::Float => write!(f, "float"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ProblemKind {
+    MissingProperty {
+        name: &'static str,
+        expected: PropertyType,
+    },
+    WrongType {
+        name: &'static str,
+        expected: PropertyType,
+    },
+    /// A layer the game does not know about, probably a typo.
+    UnknownLayer,
+    /// The layer or object the level needs is not there.
+    Missing(&'static str),
+    /// A waypoint refers to a guard that does not exist.
+    UnknownGuard(i32),
+    ZeroSize,
+}
+
+/// Everything needed to find the problem in Tiled.
+#[derive(Debug, Clone)]
+pub struct MapProblem {
+    pub layer: String,
+    pub object: Option<u32>,
+    pub kind: ProblemKind,
+}
+
+impl Display for MapProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "layer {}", self.layer)?;
+        if let Some(id) = self.object {
+            write!(f, ", object {}", id)?;
+        }
+        match &self.kind {
+            ProblemKind::MissingProperty { name, expected } => {
+                write!(f, ": missing {} property {}", expected, name)
+            }
+            ProblemKind::WrongType { name, expected } => {
+                write!(f, ": property {} should be a {}", name, expected)
+            }
+            ProblemKind::UnknownLayer => write!(f, ": unknown object layer"),
+            ProblemKind::Missing(what) => write!(f, ": no {}", what),
+            ProblemKind::UnknownGuard(id) => write!(f, ": no guard with id {}", id),
+            ProblemKind::ZeroSize => write!(f, ": has no width or height"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SweepLayout {
+    /// Radians per second.
+    pub speed: f32,
+    pub range: Option<f32>,
+    pub pause: f32,
+}
+
+/// Angles are in radians.
+#[derive(Debug, Clone)]
+pub struct CameraLayout {
+    pub pos: Vec2,
+    pub radius: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+    pub sweep: Option<SweepLayout>,
+}
+
+// defaults for the guard properties left out in the map
+// pixels per second
+pub const GUARD_SPEED: f32 = 90.;
+pub const GUARD_RADIUS: f32 = 250.;
+// in degrees, same as in Tiled
+pub const GUARD_FOV: f32 = 60.;
+
+/// Properties left out in the map are `None`, the game picks defaults for them.
+#[derive(Debug, Clone)]
+pub struct GuardLayout {
+    pub waypoints: Vec<Vec2>,
+    pub looped: bool,
+    pub speed: Option<f32>,
+    pub radius: Option<f32>,
+    /// Radians.
+    pub fov: Option<f32>,
+}
+
+/// A map that passed validation, in game coordinates.
+#[derive(Debug, Clone)]
+pub struct LevelLayout {
+    /// In tiles.
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: Vec2,
+    pub spawn: Vec2,
+    /// (center, size)
+    pub obstacles: Vec<(Vec3, Vec2)>,
+    pub treasures: Vec<Vec2>,
+    /// (center, size)
+    pub exits: Vec<(Vec2, Vec2)>,
+    pub cameras: Vec<CameraLayout>,
+    pub guards: Vec<GuardLayout>,
+}
+
+struct Layer<'a> {
+    name: &'a str,
+    problems: &'a mut Vec<MapProblem>,
+}
+
+impl Layer<'_> {
+    fn problem(&mut self, object: Option<u32>, kind: ProblemKind) {
+        self.problems.push(MapProblem {
+            layer: self.name.to_string(),
+            object,
+            kind,
+        });
+    }
+
+    fn property<'o>(
+        &mut self,
+        obj: &'o Object,
+        name: &'static str,
+        expected: PropertyType,
+        is_required: bool,
+    ) -> Option<&'o PropertyValue> {
+        let value = obj.properties.get(name);
+        let is_expected = matches!(
+            (expected, value),
+            (PropertyType::Bool, Some(PropertyValue::BoolValue(_)))
+                | (PropertyType::Int, Some(PropertyValue::IntValue(_)))
+                | (PropertyType::Float, Some(PropertyValue::FloatValue(_)))
+        );
+        match value {
+            _ if is_expected => value,
+            None if !is_required => None,
+            None => {
+                self.problem(
+                    Some(obj.id),
+                    ProblemKind::MissingProperty { name, expected },
+                );
+                None
+            }
+            Some(_) => {
+                self.problem(Some(obj.id), ProblemKind::WrongType { name, expected });
+                None
+            }
+        }
+    }
+
+    fn float(&mut self, obj: &Object, name: &'static str, is_required: bool) -> Option<f32> {
+        match self.property(obj, name, PropertyType::Float, is_required) {
+            Some(PropertyValue::FloatValue(x)) => Some(*x),
+            _ => None,
+        }
+    }
+
+    fn int(&mut self, obj: &Object, name: &'static str, is_required: bool) -> Option<i32> {
+        match self.property(obj, name, PropertyType::Int, is_required) {
+            Some(PropertyValue::IntValue(x)) => Some(*x),
+            _ => None,
+        }
+    }
+
+    fn bool(&mut self, obj: &Object, name: &'static str, is_required: bool) -> Option<bool> {
+        match self.property(obj, name, PropertyType::Bool, is_required) {
+            Some(PropertyValue::BoolValue(x)) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the level out of the map, or every problem found in it.
+pub fn validate(map: &Map) -> Result<LevelLayout, Vec<MapProblem>> {
+    let map_y = (map.height * map.tile_height) as f32;
+    // tiled places objects by their top left corner, y going down
+    let center =
+        |obj: &Object| Vec2::new(obj.x + obj.width / 2., (map_y - obj.y) - obj.height / 2.);
+    let mut problems = vec![];
+    let mut spawn = None;
+    let mut obstacles = vec![];
+    let mut treasures = vec![];
+    let mut exits = vec![];
+    let mut cameras = vec![];
+    let mut guards = vec![];
+    for group in map.object_groups.iter() {
+        let mut layer = Layer {
+            name: &group.name,
+            problems: &mut problems,
+        };
+        match group.name.as_str() {
+            "Obstacles" => {
+                for obj in group.objects.iter() {
+                    if obj.width <= 0. || obj.height <= 0. {
+                        layer.problem(Some(obj.id), ProblemKind::ZeroSize);
+                        continue;
+                    }
+                    obstacles.push((center(obj).extend(0.6), Vec2::new(obj.width, obj.height)));
+                }
+            }
+            "Spawn" => match group.objects.first() {
+                Some(obj) => spawn = Some(Vec2::new(obj.x, map_y - obj.y)),
+                None => layer.problem(None, ProblemKind::Missing("spawn point")),
+            },
+            "Treasure" => {
+                treasures.extend(
+                    group
+                        .objects
+                        .iter()
+                        .map(|obj| Vec2::new(obj.x, map_y - obj.y)),
+                );
+            }
+            "Exit" => {
+                for obj in group.objects.iter() {
+                    if obj.width <= 0. || obj.height <= 0. {
+                        layer.problem(Some(obj.id), ProblemKind::ZeroSize);
+                        continue;
+                    }
+                    exits.push((center(obj), Vec2::new(obj.width, obj.height)));
+                }
+            }
+            "Cameras" => {
+                for obj in group.objects.iter() {
+                    // all read before bailing, so every missing one is reported
+                    let radius = layer.float(obj, "radius", true);
+                    let start_angle = layer.float(obj, "start_angle", true);
+                    let end_angle = layer.float(obj, "end_angle", true);
+                    let speed = layer.float(obj, "sweep_speed", false);
+                    let range = layer.float(obj, "sweep_range", false);
+                    let pause = layer.float(obj, "sweep_pause", false);
+                    if let (Some(radius), Some(start_angle), Some(end_angle)) =
+                        (radius, start_angle, end_angle)
+                    {
+                        cameras.push(CameraLayout {
+                            pos: Vec2::new(obj.x, map_y - obj.y),
+                            radius,
+                            start_angle: start_angle.to_radians(),
+                            end_angle: end_angle.to_radians(),
+                            sweep: speed.map(|speed| SweepLayout {
+                                speed: speed.to_radians(),
+                                range: range.map(f32::to_radians),
+                                pause: pause.unwrap_or(0.),
+                            }),
+                        });
+                    }
+                }
+            }
+            "Guards" => {
+                // (guard id, order, position)
+                let mut waypoints = vec![];
+                let mut layer_guards = vec![];
+                for obj in group.objects.iter() {
+                    if obj.obj_type == "waypoint" {
+                        let guard = layer.int(obj, "guard", true);
+                        let order = layer.int(obj, "order", true);
+                        if let (Some(guard), Some(order)) = (guard, order) {
+                            waypoints.push((obj.id, guard, order, Vec2::new(obj.x, map_y - obj.y)));
+                        }
+                        continue;
+                    }
+                    let (route, looped) = match &obj.shape {
+                        ObjectShape::Polyline { points } => (points.clone(), false),
+                        ObjectShape::Polygon { points } => (points.clone(), true),
+                        _ => (vec![(0., 0.)], false),
+                    };
+                    let route = route
+                        .into_iter()
+                        .map(|(x, y)| Vec2::new(obj.x + x, map_y - (obj.y + y)))
+                        .collect();
+                    let looped = layer.bool(obj, "loop", false).unwrap_or(looped);
+                    let speed = layer.float(obj, "speed", false);
+                    let radius = layer.float(obj, "radius", false);
+                    let fov = layer.float(obj, "fov", false);
+                    layer_guards.push((
+                        obj.id,
+                        GuardLayout {
+                            waypoints: route,
+                            looped,
+                            speed,
+                            radius,
+                            fov: fov.map(f32::to_radians),
+                        },
+                    ));
+                }
+                for (waypoint_id, guard, _, _) in waypoints.iter() {
+                    if !layer_guards.iter().any(|(id, _)| *id as i32 == *guard) {
+                        layer.problem(Some(*waypoint_id), ProblemKind::UnknownGuard(*guard));
+                    }
+                }
+                waypoints.sort_by_key(|(_, guard, order, _)| (*guard, *order));
+                for (id, mut guard) in layer_guards {
+                    guard.waypoints.extend(
+                        waypoints
+                            .iter()
+                            .filter(|(_, guard, _, _)| *guard == id as i32)
+                            .map(|(_, _, _, pos)| *pos),
+                    );
+                    guards.push(guard);
+                }
+            }
+            _ => layer.problem(None, ProblemKind::UnknownLayer),
+        }
+    }
+    let mut require = |what: &'static str, layer: &str, is_present: bool| {
+        if !is_present {
+            problems.push(MapProblem {
+                layer: layer.to_string(),
+                object: None,
+                kind: ProblemKind::Missing(what),
+            });
+        }
+    };
+    // an empty Spawn layer is already reported
+    let has_spawn_layer = map.object_groups.iter().any(|g| g.name == "Spawn");
+    require("spawn layer", "Spawn", has_spawn_layer);
+    // the exit only opens with a treasure taken
+    require("treasure", "Treasure", !treasures.is_empty());
+    require("exit", "Exit", !exits.is_empty());
+    match spawn {
+        Some(spawn) if problems.is_empty() => Ok(LevelLayout {
+            width: map.width,
+            height: map.height,
+            tile_size: Vec2::new(map.tile_width as f32, map.tile_height as f32),
+            spawn,
+            obstacles,
+            treasures,
+            exits,
+            cameras,
+            guards,
+        }),
+        _ => Err(problems),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    const SPAWN: &str = r#"<objectgroup id="1" name="Spawn">
+  <object id="1" x="40" y="40" width="32" height="32"/>
+ </objectgroup>"#;
+    const TREASURE: &str = r#"<objectgroup id="2" name="Treasure">
+  <object id="2" x="200" y="40" width="10" height="8"/>
+ </objectgroup>"#;
+    const EXIT: &str = r#"<objectgroup id="3" name="Exit">
+  <object id="3" x="40" y="200" width="100" height="80"/>
+ </objectgroup>"#;
+
+    /// 10 by 10 tiles of 32 pixels, so game y is `320 - y`.
+    fn map(layers: &[&str]) -> Map {
+        let tmx = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.5" tiledversion="1.7.2" orientation="orthogonal" renderorder="right-up" width="10" height="10" tilewidth="32" tileheight="32" infinite="0" nextlayerid="10" nextobjectid="30">
+ {}
+</map>"#,
+            layers.join("\n ")
+        );
+        tiled::parse(tmx.as_bytes()).expect("test map is malformed")
+    }
+
+    fn camera(properties: &str) -> String {
+        format!(
+            r#"<objectgroup id="4" name="Cameras">
+  <object id="7" x="100" y="100" width="31" height="28">
+   <properties>
+    {}
+   </properties>
+  </object>
+ </objectgroup>"#,
+            properties
+        )
+    }
+
+    fn problems(layers: &[&str]) -> Vec<MapProblem> {
+        validate(&map(layers)).expect_err("map should have problems")
+    }
+
+    #[test]
+    fn shipped_maps_are_valid() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let maps: Vec<_> = fs::read_dir(&assets)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "tmx"))
+            .collect();
+        assert!(!maps.is_empty(), "no maps in {}", assets.display());
+        for path in maps {
+            let map = tiled::parse_file(&path)
+                .unwrap_or_else(|e| panic!("cant parse {}: {}", path.display(), e));
+            if let Err(problems) = validate(&map) {
+                panic!("{}: {:?}", path.display(), problems);
+            }
+        }
+    }
+
+    #[test]
+    fn reads_the_layout() {
+        let camera = camera(
+            r#"<property name="end_angle" type="float" value="-30"/>
+    <property name="radius" type="float" value="300"/>
+    <property name="start_angle" type="float" value="-90"/>"#,
+        );
+        let layout = validate(&map(&[SPAWN, TREASURE, EXIT, &camera])).unwrap();
+        assert_eq!(layout.spawn, Vec2::new(40., 280.));
+        assert_eq!(layout.treasures, vec![Vec2::new(200., 280.)]);
+        assert_eq!(
+            layout.exits,
+            vec![(Vec2::new(90., 80.), Vec2::new(100., 80.))]
+        );
+        let camera = &layout.cameras[0];
+        assert_eq!(camera.pos, Vec2::new(100., 220.));
+        assert_eq!(camera.radius, 300.);
+        assert!((camera.start_angle + std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert!(camera.sweep.is_none());
+    }
+
+    #[test]
+    fn missing_radius() {
+        let camera = camera(
+            r#"<property name="end_angle" type="float" value="-30"/>
+    <property name="start_angle" type="float" value="-90"/>"#,
+        );
+        let problems = problems(&[SPAWN, TREASURE, EXIT, &camera]);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert_eq!(problems[0].layer, "Cameras");
+        assert_eq!(problems[0].object, Some(7));
+        assert!(
+            matches!(
+                problems[0].kind,
+                ProblemKind::MissingProperty {
+                    name: "radius",
+                    expected: PropertyType::Float
+                }
+            ),
+            "{:?}",
+            problems[0]
+        );
+    }
+
+    #[test]
+    fn wrongly_typed_end_angle() {
+        // no type is a string in Tiled
+        let camera = camera(
+            r#"<property name="end_angle" value="-30"/>
+    <property name="radius" type="float" value="300"/>
+    <property name="start_angle" type="float" value="-90"/>"#,
+        );
+        let problems = problems(&[SPAWN, TREASURE, EXIT, &camera]);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert_eq!(problems[0].layer, "Cameras");
+        assert_eq!(problems[0].object, Some(7));
+        assert!(
+            matches!(
+                problems[0].kind,
+                ProblemKind::WrongType {
+                    name: "end_angle",
+                    expected: PropertyType::Float
+                }
+            ),
+            "{:?}",
+            problems[0]
+        );
+    }
+
+    #[test]
+    fn every_camera_problem_is_reported() {
+        let camera = camera(r#"<property name="end_angle" type="int" value="-30"/>"#);
+        let problems = problems(&[SPAWN, TREASURE, EXIT, &camera]);
+        let kinds: Vec<_> = problems
+            .iter()
+            .map(|problem| match problem.kind {
+                ProblemKind::MissingProperty { name, .. } => format!("missing {}", name),
+                ProblemKind::WrongType { name, .. } => format!("wrong {}", name),
+                ref kind => panic!("unexpected {:?}", kind),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec!["missing radius", "missing start_angle", "wrong end_angle"]
+        );
+    }
+
+    #[test]
+    fn missing_spawn() {
+        let problems = problems(&[TREASURE, EXIT]);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert_eq!(problems[0].layer, "Spawn");
+        assert_eq!(problems[0].object, None);
+        assert!(
+            matches!(problems[0].kind, ProblemKind::Missing("spawn layer")),
+            "{:?}",
+            problems[0]
+        );
+
+        let empty = r#"<objectgroup id="1" name="Spawn"/>"#;
+        let problems = self::problems(&[empty, TREASURE, EXIT]);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert_eq!(problems[0].layer, "Spawn");
+        assert!(
+            matches!(problems[0].kind, ProblemKind::Missing("spawn point")),
+            "{:?}",
+            problems[0]
+        );
+    }
+
+    #[test]
+    fn reads_guard_waypoints() {
+        let guards = r#"<objectgroup id="6" name="Guards">
+  <object id="11" x="100" y="100">
+   <polyline points="0,0 100,0 100,100"/>
+  </object>
+ </objectgroup>"#;
+        let layout = validate(&map(&[SPAWN, TREASURE, EXIT, guards])).unwrap();
+        assert_eq!(
+            layout.guards[0].waypoints,
+            vec![
+                Vec2::new(100., 220.),
+                Vec2::new(200., 220.),
+                Vec2::new(200., 120.)
+            ]
+        );
+    }
+}
@@ -1,37 +1,62 @@
-use bevy::{math::Vec2, prelude::*};
+use bevy::{
+    math::{Vec2, Vec3Swizzles},
+    prelude::*,
+};
 use bevy_ecs_tilemap::TiledMap;
 
-use crate::{map::Boundaries, GameState, MainCamera};
+use crate::{boundaries::Boundaries, geometry::PLAYER_SIZE, player::Player, GameState, MainCamera};
 
-/// Only uses x, y.
+/// Only uses x, y. In pixels per second.
 #[derive(Debug, Default)]
 pub struct Velocity(pub Vec2);
 
+// how far to stay from the walls, so the next move doesn't start touching them
+const SKIN: f32 = 0.01;
+// a corner takes two
+const MAX_SLIDES: usize = 3;
+
+/// Position after moving for `delta` seconds. Stops right at the obstacles and slides along them,
+/// the whole path is checked so fast movement can't skip through thin walls.
+pub fn step(boundaries: &Boundaries, pos: Vec2, velocity: Vec2, delta: f32) -> Vec2 {
+    let half_size = Vec2::splat(PLAYER_SIZE / 2.);
+    let mut pos = pos;
+    let mut motion = velocity * delta;
+    for _ in 0..MAX_SLIDES {
+        if motion == Vec2::ZERO {
+            break;
+        }
+        match boundaries.sweep(pos, half_size, motion) {
+            Some((t, normal)) => {
+                pos += motion * t + normal * SKIN;
+                // what is left of the move, without the part going into the wall
+                let rest = motion * (1. - t);
+                motion = rest - normal * rest.dot(normal);
+            }
+            None => {
+                pos += motion;
+                break;
+            }
+        }
+    }
+    pos
+}
+
 fn move_system(
-    query: Query<(&mut Transform, &Velocity), Without<MainCamera>>,
+    query: Query<(&mut Transform, &Velocity, Option<&Player>), Without<MainCamera>>,
     mut camera: Query<&mut Transform, With<MainCamera>>,
     boundaries: Res<Boundaries>,
+    time: Res<Time>,
 ) {
     let mut cam = camera.single_mut().expect("camera doesnt exist");
-    query.for_each_mut(|(mut trnsf, velocity)| {
-        let mut x = trnsf.translation.x;
-        let mut y = trnsf.translation.y;
-        if boundaries
-            .collide(Vec3::new(x + velocity.0.x, y, 0.))
-            .is_none()
-        {
-            x += velocity.0.x;
+    let delta = time.delta_seconds();
+    query.for_each_mut(|(mut trnsf, velocity, player)| {
+        let pos = step(&boundaries, trnsf.translation.xy(), velocity.0, delta);
+        trnsf.translation.x = pos.x;
+        trnsf.translation.y = pos.y;
+        if player.is_some() {
+            cam.translation.x = pos.x;
+            cam.translation.y = pos.y;
         }
-        if boundaries
-            .collide(Vec3::new(x, y + velocity.0.y, 0.))
-            .is_none()
-        {
-            y += velocity.0.y;
-        }
-        trnsf.translation.x = x;
-        trnsf.translation.y = y;
-        cam.translation.x = x;
-        cam.translation.y = y;
     });
 }
 
@@ -45,3 +70,89 @@ impl Plugin for MovementPlugin {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+
+    use super::*;
+    use crate::player::{DASH_VEL_MULTI, PLAYER_SPEED};
+
+    const HALF: f32 = PLAYER_SIZE / 2.;
+    // where the box stops before the walls starting at 50
+    const CONTACT: f32 = 50. - HALF - SKIN;
+
+    fn obstacle(x: f32, y: f32, width: f32, height: f32) -> (Vec3, Vec2) {
+        (Vec3::new(x, y, 0.), Vec2::new(width, height))
+    }
+
+    fn assert_near(a: Vec2, b: Vec2) {
+        assert!((a - b).abs().max_element() < 1e-3, "{} is not {}", a, b);
+    }
+
+    #[test]
+    fn distance_does_not_depend_on_frames() {
+        let bounds = Boundaries::default();
+        let velocity = Vec2::new(PLAYER_SPEED, -PLAYER_SPEED) * std::f32::consts::FRAC_1_SQRT_2;
+        let one_frame = step(&bounds, Vec2::ZERO, velocity, 1. / 30.);
+        let mut two_frames = Vec2::ZERO;
+        for _ in 0..2 {
+            two_frames = step(&bounds, two_frames, velocity, 1. / 60.);
+        }
+        assert_near(one_frame, velocity / 30.);
+        assert_near(two_frames, velocity / 30.);
+        assert!((one_frame.length() - PLAYER_SPEED / 30.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn stops_at_the_wall() {
+        // left side at x = 50
+        let bounds = Boundaries::new(vec![obstacle(60., 0., 20., 200.)]);
+        let pos = step(&bounds, Vec2::ZERO, Vec2::new(100., 0.), 1.);
+        assert_near(pos, Vec2::new(CONTACT, 0.));
+        assert!(pos.x + HALF < 50.);
+    }
+
+    #[test]
+    fn slides_along_the_wall() {
+        let bounds = Boundaries::new(vec![obstacle(60., 0., 20., 200.)]);
+        let pos = step(&bounds, Vec2::ZERO, Vec2::new(100., 100.), 1.);
+        // x stops at the wall, y keeps the whole move
+        assert_near(pos, Vec2::new(CONTACT, 100.));
+
+        let bounds = Boundaries::new(vec![obstacle(0., 60., 200., 20.)]);
+        let pos = step(&bounds, Vec2::ZERO, Vec2::new(-30., 100.), 1.);
+        assert_near(pos, Vec2::new(-30., CONTACT));
+    }
+
+    #[test]
+    fn stops_in_a_corner() {
+        // a wall on the right and one above, meeting at (50, 50)
+        let bounds = Boundaries::new(vec![
+            obstacle(60., 0., 20., 200.),
+            obstacle(0., 60., 200., 20.),
+        ]);
+        let pos = step(&bounds, Vec2::ZERO, Vec2::new(100., 100.), 1.);
+        assert_near(pos, Vec2::splat(CONTACT));
+
+        // shallower, slides into the corner along the right wall
+        let pos = step(&bounds, Vec2::ZERO, Vec2::new(100., 20.), 1.);
+        assert_near(pos, Vec2::new(CONTACT, 20.));
+        let pos = step(&bounds, pos, Vec2::new(100., 100.), 1.);
+        assert_near(pos, Vec2::splat(CONTACT));
+    }
+
+    #[test]
+    fn dash_does_not_tunnel() {
+        // thinner than a single frame of dashing at low frame rates
+        let bounds = Boundaries::new(vec![obstacle(100., 0., 2., 200.)]);
+        let velocity = Vec2::new(PLAYER_SPEED * DASH_VEL_MULTI, 0.);
+        for delta in [1. / 144., 1. / 60., 1. / 10., 0.5] {
+            let mut pos = Vec2::ZERO;
+            for _ in 0..(1. / delta) as usize {
+                pos = step(&bounds, pos, velocity, delta);
+            }
+            assert_near(pos, Vec2::new(99. - HALF - SKIN, 0.));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use bevy::{log, prelude::*, sprite::collide_aabb};
+
+use crate::{
+    geometry::PLAYER_SIZE, main_menu_ui::SelectedLevel, player::Player, progress::Progress,
+    GameState,
+};
+
+/// Where the player leaves the level, opens once a treasure is taken.
+pub struct ExitZone {
+    pub size: Vec2,
+}
+
+#[derive(Debug, Default)]
+pub struct LevelObjective {
+    pub treasures_collected: u32,
+    /// Times the cameras started noticing the player.
+    pub detections: u32,
+    /// Seconds spent in the level, not counting the reward screen.
+    pub time: f32,
+    /// Set on completion or game over, so resuming the level goes back to the menu.
+    pub is_over: bool,
+}
+
+impl LevelObjective {
+    pub fn is_exit_open(&self) -> bool {
+        self.treasures_collected > 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelResult {
+    pub level: u32,
+    pub time: f32,
+    pub treasures: u32,
+    pub detections: u32,
+}
+
+struct ExitMaterials {
+    closed: Handle<ColorMaterial>,
+    open: Handle<ColorMaterial>,
+}
+
+fn reset_objective(mut objective: ResMut<LevelObjective>) {
+    *objective = LevelObjective::default();
+}
+
+fn spawn_exit(
+    mut commands: Commands,
+    query: Query<(Entity, &Transform, &ExitZone), Added<ExitZone>>,
+    materials: Res<ExitMaterials>,
+) {
+    query.for_each(|(entity, tr, exit)| {
+        commands.entity(entity).insert_bundle(SpriteBundle {
+            sprite: Sprite::new(exit.size),
+            material: materials.closed.clone(),
+            transform: *tr,
+            ..Default::default()
+        });
+    });
+}
+
+fn update_exit_material(
+    objective: Res<LevelObjective>,
+    materials: Res<ExitMaterials>,
+    exits: Query<&mut Handle<ColorMaterial>, With<ExitZone>>,
+) {
+    if objective.is_changed() {
+        let material = if objective.is_exit_open() {
+            &materials.open
+        } else {
+            &materials.closed
+        };
+        exits.for_each_mut(|mut handle| {
+            if *handle != *material {
+                *handle = material.clone();
+            }
+        });
+    }
+}
+
+fn tick_time(mut objective: ResMut<LevelObjective>, time: Res<Time>) {
+    objective.time += time.delta_seconds();
+}
+
+fn exit_collide(
+    mut objective: ResMut<LevelObjective>,
+    player: Query<&Transform, With<Player>>,
+    exits: Query<(&Transform, &ExitZone)>,
+    mut state: ResMut<State<GameState>>,
+    mut result: ResMut<Option<LevelResult>>,
+    mut progress: ResMut<Progress>,
+    level: Res<SelectedLevel>,
+) {
+    if !objective.is_exit_open() {
+        return;
+    }
+    let tr = player.single().expect("single player").translation;
+    for (exit_tr, exit) in exits.iter() {
+        if collide_aabb::collide(tr, Vec2::splat(PLAYER_SIZE), exit_tr.translation, exit.size)
+            .is_some()
+        {
+            objective.is_over = true;
+            let level_result = LevelResult {
+                level: level.0,
+                time: objective.time,
+                treasures: objective.treasures_collected,
+                detections: objective.detections,
+            };
+            log::info!(?level_result, "level complete");
+            progress.record(&level_result);
+            *result = Some(level_result);
+            state
+                .push(GameState::LevelComplete)
+                .expect("cant move to level complete");
+            return;
+        }
+    }
+}
+
+fn fail_level(mut objective: ResMut<LevelObjective>) {
+    objective.is_over = true;
+}
+
+impl FromWorld for ExitMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world
+            .get_resource_mut::<Assets<ColorMaterial>>()
+            .expect("no materials");
+        let closed = materials.add(Color::rgba(0.3, 0.3, 0.3, 0.3).into());
+        let open = materials.add(Color::rgba(0.1, 0.8, 0.2, 0.4).into());
+        ExitMaterials { closed, open }
+    }
+}
+
+pub struct ObjectivePlugin;
+impl Plugin for ObjectivePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<LevelObjective>()
+            .init_resource::<Option<LevelResult>>()
+            .init_resource::<ExitMaterials>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Level).with_system(reset_objective.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Level)
+                    .with_system(spawn_exit.system())
+                    .with_system(update_exit_material.system())
+                    .with_system(tick_time.system())
+                    .with_system(exit_collide.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver).with_system(fail_level.system()),
+            );
+    }
+}
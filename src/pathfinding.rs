@@ -0,0 +1,355 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::math::Vec2;
+
+use crate::boundaries::Boundaries;
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// Walkability of the level, one cell per tile. World origin is the bottom left corner of the map.
+#[derive(Debug, Default, Clone)]
+pub struct NavGrid {
+    width: usize,
+    height: usize,
+    cell_size: Vec2,
+    walkable: Vec<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OpenNode {
+    // g + h
+    estimate: u32,
+    index: usize,
+}
+
+// reversed, so BinaryHeap pops the cheapest node first
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .cmp(&self.estimate)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavGrid {
+    /// `is_blocked` is asked once per cell with the cell center in world coordinates.
+    pub fn new(
+        width: usize,
+        height: usize,
+        cell_size: Vec2,
+        is_blocked: impl Fn(Vec2) -> bool,
+    ) -> Self {
+        let mut grid = NavGrid {
+            width,
+            height,
+            cell_size,
+            walkable: vec![true; width * height],
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let center = grid.cell_center((x, y));
+                grid.walkable[y * width + x] = !is_blocked(center);
+            }
+        }
+        grid
+    }
+
+    /// Cells the player fits in when standing in the middle, the grid the game builds for a level.
+    pub fn from_boundaries(
+        width: usize,
+        height: usize,
+        cell_size: Vec2,
+        boundaries: &Boundaries,
+    ) -> Self {
+        NavGrid::new(width, height, cell_size, |center| {
+            boundaries.collide(center.extend(0.)).is_some()
+        })
+    }
+
+    pub fn cell_at(&self, pos: Vec2) -> Option<(usize, usize)> {
+        if pos.x < 0. || pos.y < 0. {
+            return None;
+        }
+        let x = (pos.x / self.cell_size.x) as usize;
+        let y = (pos.y / self.cell_size.y) as usize;
+        if x < self.width && y < self.height {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    pub fn cell_center(&self, (x, y): (usize, usize)) -> Vec2 {
+        Vec2::new(x as f32 + 0.5, y as f32 + 0.5) * self.cell_size
+    }
+
+    pub fn is_walkable(&self, (x, y): (usize, usize)) -> bool {
+        x < self.width && y < self.height && self.walkable[y * self.width + x]
+    }
+
+    /// Shortest route between two world positions as a list of points to walk through,
+    /// ending exactly at `to`. `None` if either end is outside the map, blocked or unreachable.
+    /// A blocked `from` starts at the closest free cell around it instead, agents pushed against
+    /// a wall often stand in one.
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let cell = self.cell_at(from)?;
+        let start = if self.is_walkable(cell) {
+            cell
+        } else {
+            self.closest_free_neighbour(cell, from)?
+        };
+        let goal = self.cell_at(to)?;
+        let cells = self.find_cell_path(start, goal)?;
+        // the agent is in the first cell already, unless it has to step out of a blocked one
+        let skip = if start == cell { 1 } else { 0 };
+        let mut path: Vec<_> = cells
+            .into_iter()
+            .skip(skip)
+            .map(|cell| self.cell_center(cell))
+            .collect();
+        path.pop();
+        path.push(to);
+        Some(path)
+    }
+
+    /// A* over the grid, 8 directions, no cutting corners of blocked cells.
+    pub fn find_cell_path(
+        &self,
+        start: (usize, usize),
+        goal: (usize, usize),
+    ) -> Option<Vec<(usize, usize)>> {
+        if !self.is_walkable(start) || !self.is_walkable(goal) {
+            return None;
+        }
+        let start = self.index(start);
+        let goal = self.index(goal);
+        let mut cost = vec![u32::MAX; self.walkable.len()];
+        let mut came_from = vec![usize::MAX; self.walkable.len()];
+        let mut open = BinaryHeap::new();
+        cost[start] = 0;
+        open.push(OpenNode {
+            estimate: self.heuristic(start, goal),
+            index: start,
+        });
+
+        while let Some(OpenNode { estimate, index }) = open.pop() {
+            if index == goal {
+                return Some(self.rebuild_path(&came_from, start, goal));
+            }
+            // stale entry, a cheaper way here was found already
+            if estimate > cost[index] + self.heuristic(index, goal) {
+                continue;
+            }
+            for (neighbour, step) in self.neighbours(index) {
+                let next_cost = cost[index] + step;
+                if next_cost < cost[neighbour] {
+                    cost[neighbour] = next_cost;
+                    came_from[neighbour] = index;
+                    open.push(OpenNode {
+                        estimate: next_cost + self.heuristic(neighbour, goal),
+                        index: neighbour,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    fn closest_free_neighbour(&self, (x, y): (usize, usize), pos: Vec2) -> Option<(usize, usize)> {
+        let (x, y) = (x as i64, y as i64);
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+            .filter(|&(x, y)| x >= 0 && y >= 0)
+            .map(|(x, y)| (x as usize, y as usize))
+            .filter(|cell| self.is_walkable(*cell))
+            .min_by(|a, b| {
+                let a = (self.cell_center(*a) - pos).length_squared();
+                let b = (self.cell_center(*b) - pos).length_squared();
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+            })
+    }
+
+    fn index(&self, (x, y): (usize, usize)) -> usize {
+        y * self.width + x
+    }
+
+    fn cell(&self, index: usize) -> (usize, usize) {
+        (index % self.width, index / self.width)
+    }
+
+    // octile distance
+    fn heuristic(&self, from: usize, to: usize) -> u32 {
+        let (fx, fy) = self.cell(from);
+        let (tx, ty) = self.cell(to);
+        let dx = (fx as i64 - tx as i64).abs() as u32;
+        let dy = (fy as i64 - ty as i64).abs() as u32;
+        STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+    }
+
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = (usize, u32)> + '_ {
+        let (x, y) = self.cell(index);
+        let (x, y) = (x as i64, y as i64);
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|&(dx, dy)| dx != 0 || dy != 0)
+            .filter_map(move |(dx, dy)| {
+                let walkable =
+                    |x: i64, y: i64| x >= 0 && y >= 0 && self.is_walkable((x as usize, y as usize));
+                if !walkable(x + dx, y + dy) {
+                    return None;
+                }
+                let is_diagonal = dx != 0 && dy != 0;
+                if is_diagonal && (!walkable(x + dx, y) || !walkable(x, y + dy)) {
+                    return None;
+                }
+                let next = self.index(((x + dx) as usize, (y + dy) as usize));
+                let step = if is_diagonal {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                Some((next, step))
+            })
+    }
+
+    fn rebuild_path(&self, came_from: &[usize], start: usize, goal: usize) -> Vec<(usize, usize)> {
+        let mut path = vec![self.cell(goal)];
+        let mut current = goal;
+        while current != start {
+            current = came_from[current];
+            path.push(self.cell(current));
+        }
+        path.reverse();
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `#` is blocked, the first row is `y = 0`. Cells are 10 pixels wide.
+    fn grid(rows: &[&str]) -> NavGrid {
+        let width = rows[0].len();
+        let height = rows.len();
+        NavGrid::new(width, height, Vec2::new(10., 10.), |center| {
+            let (x, y) = ((center.x / 10.) as usize, (center.y / 10.) as usize);
+            rows[y].as_bytes()[x] == b'#'
+        })
+    }
+
+    fn cost(path: &[(usize, usize)]) -> u32 {
+        path.windows(2)
+            .map(|step| {
+                let (a, b) = (step[0], step[1]);
+                let (dx, dy) = (a.0 as i64 - b.0 as i64, a.1 as i64 - b.1 as i64);
+                assert!(dx.abs() <= 1 && dy.abs() <= 1, "{:?} jumps", step);
+                if dx != 0 && dy != 0 {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                }
+            })
+            .sum()
+    }
+
+    #[test]
+    fn straight_path() {
+        let grid = grid(&["....."]);
+        let path = grid.find_cell_path((0, 0), (4, 0)).unwrap();
+        assert_eq!(path, vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn diagonal_path() {
+        let grid = grid(&["....", "....", "....", "...."]);
+        let path = grid.find_cell_path((0, 0), (3, 3)).unwrap();
+        assert_eq!(path, vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn path_is_octile_optimal() {
+        let grid = grid(&["......", "......", "......", "......"]);
+        let path = grid.find_cell_path((0, 0), (5, 2)).unwrap();
+        assert_eq!(
+            cost(&path),
+            5 * STRAIGHT_COST + 2 * (DIAGONAL_COST - STRAIGHT_COST)
+        );
+        assert_eq!(
+            cost(&path),
+            grid.heuristic(grid.index((0, 0)), grid.index((5, 2)))
+        );
+    }
+
+    #[test]
+    fn detour_around_a_wall() {
+        let grid = grid(&["......", ".####.", "......"]);
+        let path = grid.find_cell_path((0, 1), (5, 1)).unwrap();
+        // down, along the wall and back up, both corners of the wall are cut off
+        assert_eq!(cost(&path), 7 * STRAIGHT_COST);
+    }
+
+    #[test]
+    fn does_not_cut_corners() {
+        let grid = grid(&[".#", ".."]);
+        let path = grid.find_cell_path((0, 0), (1, 1)).unwrap();
+        assert_eq!(path, vec![(0, 0), (0, 1), (1, 1)]);
+
+        // only a diagonal between two blocked cells connects these
+        let grid = self::grid(&["#.", ".#"]);
+        assert_eq!(grid.find_cell_path((1, 0), (0, 1)), None);
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        let grid = grid(&["..#..", "..#..", "..#.."]);
+        assert_eq!(grid.find_cell_path((0, 0), (4, 2)), None);
+    }
+
+    #[test]
+    fn blocked_or_outside_ends() {
+        let grid = grid(&["...", ".#.", "..."]);
+        assert_eq!(grid.find_cell_path((1, 1), (0, 0)), None);
+        assert_eq!(grid.find_cell_path((0, 0), (1, 1)), None);
+        assert_eq!(grid.find_cell_path((0, 0), (3, 0)), None);
+        assert_eq!(grid.find_cell_path((0, 5), (0, 0)), None);
+        assert_eq!(grid.find_path(Vec2::new(-5., 5.), Vec2::new(25., 5.)), None);
+        assert_eq!(grid.find_path(Vec2::new(5., 5.), Vec2::new(25., 35.)), None);
+    }
+
+    #[test]
+    fn world_path_ends_at_the_target() {
+        let grid = grid(&["....."]);
+        let path = grid
+            .find_path(Vec2::new(5., 5.), Vec2::new(37., 4.))
+            .unwrap();
+        assert_eq!(
+            path,
+            vec![Vec2::new(15., 5.), Vec2::new(25., 5.), Vec2::new(37., 4.)]
+        );
+    }
+
+    #[test]
+    fn steps_out_of_a_blocked_start() {
+        let grid = grid(&["....", ".#..", "...."]);
+        // inside the blocked cell, closer to the one on its right
+        let path = grid
+            .find_path(Vec2::new(18., 15.), Vec2::new(35., 25.))
+            .unwrap();
+        assert_eq!(path, vec![Vec2::new(25., 15.), Vec2::new(35., 25.)]);
+        // boxed in
+        let grid = self::grid(&["###", "###", "###", "..."]);
+        assert_eq!(
+            grid.find_path(Vec2::new(15., 15.), Vec2::new(5., 35.)),
+            None
+        );
+    }
+}
@@ -1,16 +1,23 @@
-use bevy::{log, prelude::*};
+use bevy::{log, math::Vec3Swizzles, prelude::*};
 
 use crate::{
+    boundaries::Boundaries,
     cleanup::cleanup_system,
+    effects::{ActiveEffects, Decoy, FollowPlayer, DECOY_DURATION},
+    emp::{EmpPulse, EMP_PULSE_TIME, EMP_RADIUS},
+    geometry::PLAYER_SIZE,
+    items::PlayerStatsMods,
+    mana::Mana,
     map::SpawnPoint,
     movement::Velocity,
+    objective::LevelObjective,
     skills::SkillsState,
     smoke_bomb::{SmokeBomb, SMOKE_BOMB_RADIUS},
-    GameState,
+    GameState, MainCamera,
 };
 
-pub const PLAYER_SIZE: f32 = 32.;
-const PLAYER_SPEED: f32 = 2.;
+// pixels per second
+pub const PLAYER_SPEED: f32 = 120.;
 pub const LIGHT_RADIUS: f32 = 3000.;
 
 const DASH_CAST_TIME: f32 = 0.0;
@@ -21,11 +28,18 @@ const DASH_DURATION: f32 = 0.5;
 const SMOKE_DURATION: f32 = 50.0;
 const EMP_DURATION: f32 = 1.5;
 
-const DASH_CD_TIME: f32 = 0.0;
+const DASH_CD_TIME: f32 = 2.0;
 const SMOKE_CD_TIME: f32 = 5.0;
 const EMP_CD_TIME: f32 = 10.0;
 
-const DASH_VEL_MULTI: f32 = 3.;
+const DASH_MANA_COST: f32 = 10.;
+const SMOKE_MANA_COST: f32 = 25.;
+const EMP_MANA_COST: f32 = 40.;
+
+pub const DASH_VEL_MULTI: f32 = 3.;
+const TELEPORT_DISTANCE: f32 = 200.;
+// how far back to look for a free spot when the target is inside an obstacle
+const TELEPORT_STEP: f32 = PLAYER_SIZE / 4.;
 
 pub struct LevelMarker;
 
@@ -55,6 +69,18 @@ enum CastingCommand {
     Interrupt,
 }
 
+/// Effective values of a skill after gear is applied, in seconds and pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpellParams {
+    pub cast_time: f32,
+    pub duration: f32,
+    pub cooldown: f32,
+    /// Zero for skills without an area.
+    pub radius: f32,
+    pub mana_cost: f32,
+    pub charges: u32,
+}
+
 impl SpellKind {
     fn cast_time(&self) -> f32 {
         match self {
@@ -79,6 +105,69 @@ impl SpellKind {
             SpellKind::Emp => EMP_DURATION,
         }
     }
+
+    fn radius(&self) -> f32 {
+        match self {
+            SpellKind::Dash => 0.,
+            SpellKind::Smoke => SMOKE_BOMB_RADIUS,
+            SpellKind::Emp => EMP_RADIUS,
+        }
+    }
+
+    fn mana_cost(&self) -> f32 {
+        match self {
+            SpellKind::Dash => DASH_MANA_COST,
+            SpellKind::Smoke => SMOKE_MANA_COST,
+            SpellKind::Emp => EMP_MANA_COST,
+        }
+    }
+
+    pub fn params(&self, stats: &PlayerStatsMods) -> SpellParams {
+        let mana_cost = if *self == SpellKind::Dash && stats.dash_no_mana_cost {
+            0.
+        } else {
+            (self.mana_cost() - stats.mana_cost).max(0.)
+        };
+        SpellParams {
+            // there is no cast speed stat
+            cast_time: self.cast_time(),
+            duration: stats.scale_duration(self.duration()),
+            cooldown: stats.scale_cooldown(self.cd_time()),
+            radius: stats.scale_area(self.radius()),
+            mana_cost,
+            charges: match self {
+                SpellKind::Dash => 1 + stats.dash_charges,
+                _ => 1,
+            },
+        }
+    }
+}
+
+fn can_cast(
+    kind: SpellKind,
+    skills_state: &SkillsState,
+    mana: &Mana,
+    stats: &PlayerStatsMods,
+) -> bool {
+    skills_state.get_state(kind).is_ready() && mana.can_afford(kind.params(stats).mana_cost)
+}
+
+/// Furthest free spot from `from` towards `dir` before the first wall on the way, the player
+/// stays in place if there is none.
+fn teleport_target(boundaries: &Boundaries, from: Vec2, dir: Vec2, distance: f32) -> Vec2 {
+    let dir = dir.normalize_or_zero();
+    let half_size = Vec2::splat(PLAYER_SIZE / 2.);
+    let mut distance = boundaries
+        .sweep(from, half_size, dir * distance)
+        .map_or(distance, |(fraction, _)| fraction * distance);
+    while distance > 0. {
+        let target = from + dir * distance;
+        if boundaries.collide(target.extend(0.)).is_none() {
+            return target;
+        }
+        distance -= TELEPORT_STEP;
+    }
+    from
 }
 
 struct DurationSpell(Timer);
@@ -87,17 +176,29 @@ struct MainTexture(Handle<ColorMaterial>);
 
 #[derive(Debug)]
 struct LastVelocity(Vec2);
+impl LastVelocity {
+    // facing down, same as the sprite
+    fn new(speed: f32) -> Self {
+        Self(Vec2::new(0., -speed))
+    }
+}
 impl Default for LastVelocity {
     fn default() -> Self {
-        // TODO: init with modified player speed
-        Self(Vec2::new(0., -PLAYER_SPEED))
+        Self::new(PLAYER_SPEED)
     }
 }
 pub struct Dashing(Timer);
 
 pub struct Player;
 
-fn spawn_player(mut commands: Commands, main_tex: Res<MainTexture>, spawn: Res<SpawnPoint>) {
+fn spawn_player(
+    mut commands: Commands,
+    main_tex: Res<MainTexture>,
+    spawn: Res<SpawnPoint>,
+    stats: Res<PlayerStatsMods>,
+    mut last_velocity: ResMut<LastVelocity>,
+) {
+    *last_velocity = LastVelocity::new(stats.scale_movement_speed(PLAYER_SPEED));
     let sprite = Sprite::new(Vec2::splat(PLAYER_SIZE));
 
     let spawn = spawn.0.expect("loaded");
@@ -115,12 +216,24 @@ fn spawn_player(mut commands: Commands, main_tex: Res<MainTexture>, spawn: Res<S
         .insert(Velocity::default());
 }
 
+/// Diagonals are normalized, so they are not faster than straight lines.
+fn input_velocity(up: bool, down: bool, left: bool, right: bool, speed: f32) -> Vec2 {
+    let axis = |positive, negative| match (positive, negative) {
+        (true, false) => 1.,
+        (false, true) => -1.,
+        _ => 0.,
+    };
+    let dir = Vec2::new(axis(right, left), axis(up, down));
+    dir.normalize_or_zero() * speed
+}
+
 fn move_keyboard(
     keys: Res<Input<KeyCode>>,
     mut query: Query<&mut Velocity, (With<Player>, Without<Dashing>)>,
     mut casting_events: EventWriter<CastingCommand>,
     cast_res: Res<Option<Casting>>,
     mut last_velocity: ResMut<LastVelocity>,
+    stats: Res<PlayerStatsMods>,
 ) {
     let mut velocity = if let Ok(x) = query.single_mut() {
         x
@@ -134,21 +247,10 @@ fn move_keyboard(
     let q = keys.pressed(KeyCode::Q);
     let e = keys.pressed(KeyCode::E);
     let r = keys.pressed(KeyCode::R);
-    velocity.0.x = 0.;
-    velocity.0.y = 0.;
+    velocity.0 = Vec2::ZERO;
+    let speed = stats.scale_movement_speed(PLAYER_SPEED);
     if cast_res.is_none() {
-        if up {
-            velocity.0.y += PLAYER_SPEED;
-        }
-        if down {
-            velocity.0.y -= PLAYER_SPEED;
-        }
-        if right {
-            velocity.0.x += PLAYER_SPEED;
-        }
-        if left {
-            velocity.0.x -= PLAYER_SPEED;
-        }
+        velocity.0 = input_velocity(up, down, left, right, speed);
         if velocity.0 != Vec2::ZERO {
             last_velocity.0 = velocity.0;
         }
@@ -200,7 +302,6 @@ fn dash(
 ) {
     let events: Vec<_> = casting_events.iter().collect();
     query.for_each_mut(|(entity, mut dashing, mut velocity)| {
-        // TODO: normalize per frame
         let delta = time.delta();
         let mut needs_interrupt = false;
         if dashing.0.tick(delta).just_finished() {
@@ -233,13 +334,21 @@ fn start_dash(
     query.for_each_mut(|mut velocity| velocity.0 = last_velocity.0 * DASH_VEL_MULTI);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn process_casting(
     mut commands: Commands,
     mut casting_events: EventReader<CastingCommand>,
     mut cast_res: ResMut<Option<Casting>>,
-    player: Query<(Entity, &Transform), With<Player>>,
+    mut player: Query<(Entity, &mut Transform), With<Player>>,
+    mut camera: Query<&mut Transform, (With<MainCamera>, Without<Player>)>,
     time: Res<Time>,
     mut skills_state: ResMut<SkillsState>,
+    stats: Res<PlayerStatsMods>,
+    mut mana: ResMut<Mana>,
+    boundaries: Res<Boundaries>,
+    last_velocity: Res<LastVelocity>,
+    effects: Res<ActiveEffects>,
+    main_tex: Res<MainTexture>,
 ) {
     if cast_res.is_some() {
         if let Some(casting) = cast_res.as_mut() {
@@ -255,16 +364,14 @@ fn process_casting(
                     }
 
                     CastingCommand::Cast(cast_kind) => {
-                        let state = skills_state.get_state(*cast_kind);
-                        if state.time_to_cd.is_some() {
-                            // cd is still on
+                        if !can_cast(*cast_kind, &skills_state, &mana, &stats) {
                             return;
                         }
 
                         // TODO: remove animation
                         casting.kind = *cast_kind;
-                        // TODO: scale with stats
-                        casting.timer = Timer::from_seconds(cast_kind.cast_time(), false);
+                        let cast_time = cast_kind.params(&stats).cast_time;
+                        casting.timer = Timer::from_seconds(cast_time, false);
                         // TODO: spawn animation
                     }
                     CastingCommand::Interrupt => {
@@ -276,8 +383,10 @@ fn process_casting(
             }
         } else if let CastingCommand::Cast(cast_kind) = event {
             let kind = *cast_kind;
-            // TODO: scale with stats
-            let timer = Timer::from_seconds(cast_kind.cast_time(), false);
+            if !can_cast(kind, &skills_state, &mana, &stats) {
+                return;
+            }
+            let timer = Timer::from_seconds(cast_kind.params(&stats).cast_time, false);
             let casting = Casting { kind, timer };
             *cast_res = Some(casting);
             log::debug!("started casting");
@@ -287,37 +396,73 @@ fn process_casting(
     if let Some(casting) = cast_res.as_ref() {
         if casting.timer.just_finished() {
             log::debug!("finished casting");
-            let (player, tr) = player.single().expect("single player");
+            let (player, mut tr) = player.single_mut().expect("single player");
+            let params = casting.kind.params(&stats);
+            if casting.kind == SpellKind::Dash && effects.dash_leaves_decoy() {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite::new(Vec2::splat(PLAYER_SIZE)),
+                        material: main_tex.0.clone(),
+                        transform: *tr,
+                        ..Default::default()
+                    })
+                    .insert(DurationSpell(Timer::from_seconds(DECOY_DURATION, false)))
+                    .insert(LevelMarker)
+                    .insert(Decoy);
+                log::debug!("left a decoy");
+            }
             match casting.kind {
+                SpellKind::Dash if stats.dash_teleport => {
+                    let target = teleport_target(
+                        &boundaries,
+                        tr.translation.xy(),
+                        last_velocity.0,
+                        TELEPORT_DISTANCE,
+                    );
+                    tr.translation.x = target.x;
+                    tr.translation.y = target.y;
+                    let mut cam = camera.single_mut().expect("camera doesnt exist");
+                    cam.translation.x = target.x;
+                    cam.translation.y = target.y;
+                    log::debug!("teleported");
+                }
                 SpellKind::Dash => {
-                    let timer = Timer::from_seconds(casting.kind.duration(), false);
+                    let timer = Timer::from_seconds(params.duration, false);
                     commands.entity(player).insert(Dashing(timer));
                     log::debug!("starting dashing");
                 }
                 SpellKind::Smoke => {
-                    commands
-                        .spawn()
+                    let mut smoke = commands.spawn();
+                    smoke
                         .insert(*tr)
-                        .insert(DurationSpell(Timer::from_seconds(
-                            casting.kind.duration(),
-                            false,
-                        )))
+                        .insert(DurationSpell(Timer::from_seconds(params.duration, false)))
                         .insert(LevelMarker)
                         .insert(SmokeBomb {
-                            radius: SMOKE_BOMB_RADIUS,
+                            radius: params.radius,
                         });
+                    if effects.smoke_follows_player() {
+                        smoke.insert(FollowPlayer);
+                    }
                     log::debug!("casted smoke bomb");
                 }
-                SpellKind::Emp => {}
-            }
-            // start cd
-            let duration = casting.kind.cd_time();
-            if duration > 0. {
-                let state = skills_state.get_state_mut(casting.kind);
-                log::debug!(duration = duration, "putting skill on cd");
-                let timer = Timer::from_seconds(duration, false);
-                state.time_to_cd = Some(timer);
+                SpellKind::Emp => {
+                    commands
+                        .spawn()
+                        .insert(*tr)
+                        .insert(DurationSpell(Timer::from_seconds(EMP_PULSE_TIME, false)))
+                        .insert(LevelMarker)
+                        .insert(EmpPulse {
+                            radius: params.radius,
+                            duration: params.duration,
+                        });
+                    log::debug!("casted emp");
+                }
             }
+            mana.spend(params.mana_cost);
+            log::debug!(duration = params.cooldown, "using a charge");
+            skills_state
+                .get_state_mut(casting.kind)
+                .use_charge(params.cooldown);
             let _ = cast_res.take();
         }
     }
@@ -350,10 +495,13 @@ impl FromWorld for MainTexture {
     }
 }
 
-fn back_to_menu(mut state: ResMut<State<GameState>>) {
-    state
-        .set(GameState::MainMenu)
-        .expect("cant move back from reward screen");
+fn back_to_menu(mut state: ResMut<State<GameState>>, objective: Res<LevelObjective>) {
+    // the reward screen returns to the level, completion and game over end it
+    if objective.is_over {
+        state
+            .set(GameState::MainMenu)
+            .expect("cant move back from the level");
+    }
 }
 
 pub struct PlayerPlugin;
@@ -384,3 +532,143 @@ impl Plugin for PlayerPlugin {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagonals_are_not_faster() {
+        let straight = input_velocity(true, false, false, false, PLAYER_SPEED);
+        assert_eq!(straight, Vec2::new(0., PLAYER_SPEED));
+        for (up, down, left, right) in [
+            (true, false, false, true),
+            (true, false, true, false),
+            (false, true, false, true),
+            (false, true, true, false),
+        ] {
+            let velocity = input_velocity(up, down, left, right, PLAYER_SPEED);
+            assert!(
+                (velocity.length() - PLAYER_SPEED).abs() < 1e-3,
+                "{}",
+                velocity
+            );
+            assert!(
+                (velocity.x.abs() - velocity.y.abs()).abs() < 1e-3,
+                "{}",
+                velocity
+            );
+        }
+    }
+
+    #[test]
+    fn opposite_keys_cancel_out() {
+        assert_eq!(
+            input_velocity(true, true, false, false, PLAYER_SPEED),
+            Vec2::ZERO
+        );
+        assert_eq!(
+            input_velocity(true, true, true, true, PLAYER_SPEED),
+            Vec2::ZERO
+        );
+        assert_eq!(
+            input_velocity(true, false, true, true, PLAYER_SPEED),
+            Vec2::new(0., PLAYER_SPEED)
+        );
+    }
+
+    #[test]
+    fn base_params() {
+        let stats = PlayerStatsMods::default();
+        assert_eq!(
+            SpellKind::Dash.params(&stats),
+            SpellParams {
+                cast_time: 0.,
+                duration: 0.5,
+                cooldown: 2.,
+                radius: 0.,
+                mana_cost: 10.,
+                charges: 1,
+            }
+        );
+        assert_eq!(
+            SpellKind::Smoke.params(&stats),
+            SpellParams {
+                cast_time: 1.,
+                duration: 50.,
+                cooldown: 5.,
+                radius: 50.,
+                mana_cost: 25.,
+                charges: 1,
+            }
+        );
+        assert_eq!(
+            SpellKind::Emp.params(&stats),
+            SpellParams {
+                cast_time: 1.5,
+                duration: 1.5,
+                cooldown: 10.,
+                radius: 250.,
+                mana_cost: 40.,
+                charges: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn mods_change_params() {
+        let stats = PlayerStatsMods {
+            area_of_effect: 0.5,
+            duration: 1.,
+            cooldown_reduction: 0.25,
+            mana_cost: 15.,
+            dash_charges: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            SpellKind::Smoke.params(&stats),
+            SpellParams {
+                // no stat changes the cast time
+                cast_time: 1.,
+                duration: 100.,
+                cooldown: 4.,
+                radius: 75.,
+                mana_cost: 10.,
+                charges: 1,
+            }
+        );
+        let dash = SpellKind::Dash.params(&stats);
+        // the reduction is bigger than the cost
+        assert_eq!(dash.mana_cost, 0.);
+        assert_eq!(dash.charges, 3);
+        assert_eq!(dash.duration, 1.);
+    }
+
+    #[test]
+    fn free_dash_costs_nothing() {
+        let stats = PlayerStatsMods {
+            dash_no_mana_cost: true,
+            ..Default::default()
+        };
+        assert_eq!(SpellKind::Dash.params(&stats).mana_cost, 0.);
+        assert_eq!(SpellKind::Smoke.params(&stats).mana_cost, 25.);
+    }
+
+    #[test]
+    fn teleport_stops_before_walls() {
+        // a thin wall with free space behind it
+        let bounds = Boundaries::new(vec![(Vec3::new(100., 0., 0.), Vec2::new(10., 200.))]);
+        let target = teleport_target(&bounds, Vec2::ZERO, Vec2::X, TELEPORT_DISTANCE);
+        assert!(target.x + PLAYER_SIZE / 2. <= 95., "{}", target);
+        assert!(target.x > 0., "{}", target);
+        assert_eq!(target.y, 0.);
+
+        let open = teleport_target(
+            &Boundaries::default(),
+            Vec2::ZERO,
+            Vec2::Y,
+            TELEPORT_DISTANCE,
+        );
+        assert_eq!(open, Vec2::new(0., TELEPORT_DISTANCE));
+    }
+}
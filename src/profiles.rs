@@ -0,0 +1,248 @@
+use std::{
+    env,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{log, prelude::*};
+
+/// Overrides where profiles are stored, `--data-dir <path>` takes priority over it.
+pub const DATA_DIR_ENV: &str = "RUSTYJAM_DATA_DIR";
+const DATA_DIR_FLAG: &str = "--data-dir";
+const APP_DIR: &str = "rustyjam1";
+const PROFILES_DIR: &str = "profiles";
+const CURRENT_PROFILE_FILE: &str = "current_profile";
+const DEFAULT_PROFILE: &str = "default";
+pub const SAVE_FILE: &str = "save.json";
+// saves used to be written next to the executable
+const LEGACY_SAVE: &str = "save.json";
+pub const MAX_NAME_LEN: usize = 20;
+/// As many as fit on the profiles screen.
+pub const MAX_PROFILES: usize = 6;
+
+/// Named profiles, each is a directory with its own save.
+#[derive(Debug)]
+pub struct Profiles {
+    root: PathBuf,
+    names: Vec<String>,
+    current: usize,
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Io(io::Error),
+    InvalidName,
+    NameTaken,
+    TooMany,
+    LastProfile,
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileError::Io(e) => write!(f, "{}", e),
+            ProfileError::InvalidName => write!(
+                f,
+                "names are up to {} letters, digits, spaces, - and _",
+                MAX_NAME_LEN
+            ),
+            ProfileError::NameTaken => write!(f, "this name is taken"),
+            ProfileError::TooMany => write!(f, "there can be at most {} profiles", MAX_PROFILES),
+            ProfileError::LastProfile => write!(f, "cant delete the only profile"),
+        }
+    }
+}
+
+impl From<io::Error> for ProfileError {
+    fn from(e: io::Error) -> Self {
+        ProfileError::Io(e)
+    }
+}
+
+impl Profiles {
+    /// Creates the directories and a default profile if there are none yet.
+    pub fn open(root: PathBuf) -> io::Result<Self> {
+        let profiles_dir = root.join(PROFILES_DIR);
+        fs::create_dir_all(&profiles_dir)?;
+        let mut names = vec![];
+        for entry in fs::read_dir(&profiles_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        let mut profiles = Profiles {
+            root,
+            names,
+            current: 0,
+        };
+        if profiles.names.is_empty() {
+            let dir = profiles.profile_dir(DEFAULT_PROFILE);
+            fs::create_dir_all(&dir)?;
+            profiles.names.push(DEFAULT_PROFILE.to_string());
+            let legacy = Path::new(LEGACY_SAVE);
+            if legacy.exists() {
+                log::info!("importing {} into the default profile", legacy.display());
+                fs::copy(legacy, dir.join(SAVE_FILE))?;
+            }
+        }
+        let current =
+            fs::read_to_string(profiles.root.join(CURRENT_PROFILE_FILE)).unwrap_or_default();
+        profiles.current = profiles
+            .names
+            .iter()
+            .position(|name| *name == current.trim())
+            .unwrap_or(0);
+        Ok(profiles)
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn current(&self) -> &str {
+        &self.names[self.current]
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn save_path(&self) -> PathBuf {
+        self.profile_dir(self.current()).join(SAVE_FILE)
+    }
+
+    pub fn select(&mut self, index: usize) -> io::Result<()> {
+        self.current = index;
+        fs::write(self.root.join(CURRENT_PROFILE_FILE), self.current())
+    }
+
+    pub fn create(&mut self, name: &str) -> Result<(), ProfileError> {
+        if self.names.len() >= MAX_PROFILES {
+            return Err(ProfileError::TooMany);
+        }
+        let name = self.check_name(name, None)?;
+        fs::create_dir(self.profile_dir(&name))?;
+        let current = self.current().to_string();
+        self.names.push(name);
+        self.names.sort();
+        self.current = self.index_of(&current);
+        Ok(())
+    }
+
+    pub fn rename(&mut self, index: usize, name: &str) -> Result<(), ProfileError> {
+        // only the case may change, the name is not taken by the profile itself
+        let name = self.check_name(name, Some(index))?;
+        fs::rename(
+            self.profile_dir(&self.names[index]),
+            self.profile_dir(&name),
+        )?;
+        let current = if index == self.current {
+            name.clone()
+        } else {
+            self.current().to_string()
+        };
+        self.names[index] = name;
+        self.names.sort();
+        self.select(self.index_of(&current))?;
+        Ok(())
+    }
+
+    /// Removes the profile with its saves. The current profile moves to the first one if deleted.
+    pub fn delete(&mut self, index: usize) -> Result<(), ProfileError> {
+        if self.names.len() <= 1 {
+            return Err(ProfileError::LastProfile);
+        }
+        fs::remove_dir_all(self.profile_dir(&self.names[index]))?;
+        let current = self.current().to_string();
+        self.names.remove(index);
+        let current = self.names.iter().position(|name| *name == current);
+        self.select(current.unwrap_or(0))?;
+        Ok(())
+    }
+
+    /// `renamed` is the profile getting the name, its current one does not count as taken.
+    fn check_name(&self, name: &str, renamed: Option<usize>) -> Result<String, ProfileError> {
+        let name = name.trim();
+        let is_valid = !name.is_empty()
+            && name.chars().count() <= MAX_NAME_LEN
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_');
+        if !is_valid {
+            return Err(ProfileError::InvalidName);
+        }
+        // case insensitive file systems would mix these up
+        let taken = self
+            .names
+            .iter()
+            .enumerate()
+            .any(|(index, n)| Some(index) != renamed && n.eq_ignore_ascii_case(name));
+        if taken {
+            return Err(ProfileError::NameTaken);
+        }
+        Ok(name.to_string())
+    }
+
+    fn index_of(&self, name: &str) -> usize {
+        self.names.iter().position(|n| n == name).unwrap_or(0)
+    }
+
+    fn profile_dir(&self, name: &str) -> PathBuf {
+        self.root.join(PROFILES_DIR).join(name)
+    }
+}
+
+/// `--data-dir`, then `RUSTYJAM_DATA_DIR`, then the platform data directory.
+pub fn data_dir() -> PathBuf {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == DATA_DIR_FLAG {
+            if let Some(dir) = args.next() {
+                return PathBuf::from(dir);
+            }
+        } else if let Some(dir) = arg.strip_prefix("--data-dir=") {
+            return PathBuf::from(dir);
+        }
+    }
+    if let Some(dir) = env::var_os(DATA_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    platform_data_dir()
+        .map(|dir| dir.join(APP_DIR))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(windows)]
+fn platform_data_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn platform_data_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn platform_data_dir() -> Option<PathBuf> {
+    // relative XDG paths are invalid by the spec and should be ignored
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+}
+
+impl FromWorld for Profiles {
+    fn from_world(_world: &mut World) -> Self {
+        let dir = data_dir();
+        log::info!("profiles are in {}", dir.display());
+        Profiles::open(dir).unwrap_or_else(|e| {
+            log::error!("cant open profiles: {}, using the working directory", e);
+            Profiles::open(PathBuf::from(".")).expect("cant open profiles")
+        })
+    }
+}
@@ -0,0 +1,440 @@
+use bevy::{log, prelude::*};
+
+use crate::{
+    autosave::Autosave,
+    button::{register_my_button, ClickedButtonEvent, MyButton, MyButtonBundle},
+    cleanup::cleanup_system,
+    error_screen::ErrorMessage,
+    items::PlayerItems,
+    main_menu_ui::{change_camera_scale_from_resize, light_text_color},
+    perlin::{PerlinBundle, PerlinPipelineHandle},
+    profiles::{Profiles, MAX_NAME_LEN},
+    progress::Progress,
+    save::{self, SaveError, SaveFile},
+    GameState, RobotoFont,
+};
+
+struct ProfilesScreenMarker;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ClickedSelect(usize);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ClickedRename(usize);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ClickedDelete(usize);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ClickedNew;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ClickedBack;
+
+#[derive(Debug, Clone, Copy)]
+enum NameInput {
+    New,
+    Rename(usize),
+}
+
+/// Everything on the screen that is not stored in `Profiles`.
+#[derive(Debug, Default)]
+struct ProfilesView {
+    input: Option<NameInput>,
+    text: String,
+    /// Deleting asks for a second click.
+    confirm_delete: Option<usize>,
+    error: Option<String>,
+}
+
+struct CurrentProfilesView(Entity);
+
+const ROW_HEIGHT: f32 = 70.;
+
+fn text(value: String, font: &RobotoFont, font_size: f32, color: Color) -> Text {
+    Text::with_section(
+        value,
+        TextStyle {
+            font: font.0.clone(),
+            font_size,
+            color,
+        },
+        TextAlignment {
+            vertical: VerticalAlign::Center,
+            horizontal: HorizontalAlign::Center,
+        },
+    )
+}
+
+fn text_button<T: Default + Clone + Send + Sync + 'static>(
+    cmds: &mut ChildBuilder,
+    text: Text,
+    transform: Transform,
+    size: Vec2,
+    id: T,
+) {
+    cmds.spawn_bundle(Text2dBundle {
+        text,
+        transform,
+        ..Default::default()
+    })
+    .with_children(|cmds| {
+        cmds.spawn_bundle(MyButtonBundle {
+            button: MyButton { size, id },
+            transform: Transform::from_xyz(0., 0., 0.0001),
+            ..Default::default()
+        });
+    });
+}
+
+fn setup(
+    mut commands: Commands,
+    pp_handle: Res<PerlinPipelineHandle>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    font: Res<RobotoFont>,
+    mut view: ResMut<ProfilesView>,
+) {
+    *view = ProfilesView::default();
+    let v_pos = vec![
+        [-2000., -2000.],
+        [2000., -2000.],
+        [2000., 2000.],
+        [-2000., 2000.],
+    ];
+    let uv = [0.3; 4].to_vec();
+    let indices = vec![0, 1, 2, 0, 2, 3];
+    let mut mesh = Mesh::new(bevy::render::pipeline::PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, v_pos);
+    mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices)));
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uv);
+    commands
+        .spawn()
+        .insert(ProfilesScreenMarker)
+        .insert(Transform::default())
+        .insert(GlobalTransform::default())
+        .insert_bundle(MeshBundle {
+            mesh: meshes.add(mesh),
+            transform: Transform::from_xyz(0., 0., 0.2),
+            ..Default::default()
+        })
+        .insert_bundle(PerlinBundle::new(
+            &pp_handle,
+            1500.,
+            0.2,
+            Vec3::new(0.05, 0.05, 0.05),
+        ))
+        .with_children(|cmds| {
+            let color = light_text_color();
+            cmds.spawn_bundle(Text2dBundle {
+                text: text("Profiles".to_string(), &font, 60., color),
+                transform: Transform::from_xyz(0., 360., 0.001),
+                ..Default::default()
+            });
+            text_button(
+                cmds,
+                text("New profile".to_string(), &font, 30., color),
+                Transform::from_xyz(-120., -230., 0.001),
+                Vec2::new(200., 50.),
+                ClickedNew,
+            );
+            text_button(
+                cmds,
+                text("Back".to_string(), &font, 30., color),
+                Transform::from_xyz(120., -230., 0.001),
+                Vec2::new(200., 50.),
+                ClickedBack,
+            );
+        });
+}
+
+fn dispatch_profiles(
+    mut commands: Commands,
+    mut cur_view: ResMut<Option<CurrentProfilesView>>,
+    view: Res<ProfilesView>,
+    profiles: Res<Profiles>,
+    font: Res<RobotoFont>,
+) {
+    if !(view.is_changed() || profiles.is_changed()) && cur_view.is_some() {
+        return;
+    }
+    if let Some(CurrentProfilesView(entity)) = cur_view.take() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let color = light_text_color();
+    let next_view = commands
+        .spawn()
+        .insert(ProfilesScreenMarker)
+        .insert(Transform::from_xyz(0., 0., 0.3))
+        .insert(GlobalTransform::default())
+        .with_children(|cmds| {
+            for (index, name) in profiles.names().iter().enumerate() {
+                let y = 250. - index as f32 * ROW_HEIGHT;
+                let name = if index == profiles.current_index() {
+                    format!("> {} <", name)
+                } else {
+                    name.clone()
+                };
+                text_button(
+                    cmds,
+                    text(name, &font, 36., color),
+                    Transform::from_xyz(-150., y, 0.001),
+                    Vec2::new(360., 50.),
+                    ClickedSelect(index),
+                );
+                text_button(
+                    cmds,
+                    text("Rename".to_string(), &font, 24., color),
+                    Transform::from_xyz(140., y, 0.001),
+                    Vec2::new(120., 40.),
+                    ClickedRename(index),
+                );
+                let delete = if view.confirm_delete == Some(index) {
+                    text("Sure?".to_string(), &font, 24., Color::RED)
+                } else {
+                    text("Delete".to_string(), &font, 24., color)
+                };
+                text_button(
+                    cmds,
+                    delete,
+                    Transform::from_xyz(280., y, 0.001),
+                    Vec2::new(120., 40.),
+                    ClickedDelete(index),
+                );
+            }
+            if let Some(input) = view.input {
+                let prompt = match input {
+                    NameInput::New => "New profile",
+                    NameInput::Rename(_) => "Rename to",
+                };
+                cmds.spawn_bundle(Text2dBundle {
+                    text: text(format!("{}: {}_", prompt, view.text), &font, 36., color),
+                    transform: Transform::from_xyz(0., -310., 0.001),
+                    ..Default::default()
+                });
+                cmds.spawn_bundle(Text2dBundle {
+                    text: text(
+                        "Enter to confirm, Escape to cancel".to_string(),
+                        &font,
+                        20.,
+                        color,
+                    ),
+                    transform: Transform::from_xyz(0., -350., 0.001),
+                    ..Default::default()
+                });
+            }
+            if let Some(error) = &view.error {
+                cmds.spawn_bundle(Text2dBundle {
+                    text: text(error.clone(), &font, 24., Color::RED),
+                    transform: Transform::from_xyz(0., -390., 0.001),
+                    ..Default::default()
+                });
+            }
+        })
+        .id();
+    *cur_view = Some(CurrentProfilesView(next_view));
+}
+
+fn type_name(
+    mut chars: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut view: ResMut<ProfilesView>,
+    mut profiles: ResMut<Profiles>,
+) {
+    let input = if let Some(input) = view.input {
+        input
+    } else {
+        // typed outside of the name field
+        for _ in chars.iter() {}
+        return;
+    };
+    if keys.just_pressed(KeyCode::Escape) {
+        *view = ProfilesView::default();
+        return;
+    }
+    if keys.just_pressed(KeyCode::Return) || keys.just_pressed(KeyCode::NumpadEnter) {
+        let result = match input {
+            NameInput::New => profiles.create(&view.text),
+            NameInput::Rename(index) => profiles.rename(index, &view.text),
+        };
+        match result {
+            Ok(()) => *view = ProfilesView::default(),
+            Err(e) => {
+                log::warn!("cant apply profile name: {}", e);
+                view.error = Some(e.to_string());
+            }
+        }
+        return;
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        view.text.pop();
+    }
+    for ReceivedCharacter { char, .. } in chars.iter() {
+        if !char.is_control() && view.text.chars().count() < MAX_NAME_LEN {
+            view.text.push(*char);
+        }
+    }
+}
+
+fn clicked_new(
+    mut event_reader: EventReader<ClickedButtonEvent<ClickedNew>>,
+    mut view: ResMut<ProfilesView>,
+) {
+    if event_reader.iter().next().is_some() {
+        *view = ProfilesView {
+            input: Some(NameInput::New),
+            ..Default::default()
+        };
+    }
+}
+
+fn clicked_rename(
+    mut event_reader: EventReader<ClickedButtonEvent<ClickedRename>>,
+    mut view: ResMut<ProfilesView>,
+    profiles: Res<Profiles>,
+) {
+    if let Some(ClickedButtonEvent(ClickedRename(index))) = event_reader.iter().next() {
+        *view = ProfilesView {
+            input: Some(NameInput::Rename(*index)),
+            text: profiles.names()[*index].clone(),
+            ..Default::default()
+        };
+    }
+}
+
+fn clicked_delete(
+    mut event_reader: EventReader<ClickedButtonEvent<ClickedDelete>>,
+    mut view: ResMut<ProfilesView>,
+    mut profiles: ResMut<Profiles>,
+    mut items: ResMut<PlayerItems>,
+    mut progress: ResMut<Progress>,
+    mut autosave: ResMut<Autosave>,
+    mut error: ResMut<Option<ErrorMessage>>,
+) {
+    if let Some(ClickedButtonEvent(ClickedDelete(index))) = event_reader.iter().next() {
+        if view.confirm_delete != Some(*index) {
+            *view = ProfilesView {
+                confirm_delete: Some(*index),
+                ..Default::default()
+            };
+            return;
+        }
+        log::info!("deleting profile {}", profiles.names()[*index]);
+        let was_current = *index == profiles.current_index();
+        *view = ProfilesView::default();
+        if was_current {
+            // autosaves only ever write to the current profile
+            autosave.cancel();
+        }
+        if let Err(e) = profiles.delete(*index) {
+            log::error!("cant delete a profile: {}", e);
+            view.error = Some(e.to_string());
+            if was_current {
+                // the profile is still there, write what was cancelled
+                autosave.save_soon();
+            }
+            return;
+        }
+        if was_current {
+            if let Some(message) = save::load(&profiles.save_path(), &mut items, &mut progress) {
+                *error = Some(message);
+            }
+        }
+    }
+}
+
+fn clicked_select(
+    mut event_reader: EventReader<ClickedButtonEvent<ClickedSelect>>,
+    mut view: ResMut<ProfilesView>,
+    mut profiles: ResMut<Profiles>,
+    mut items: ResMut<PlayerItems>,
+    mut progress: ResMut<Progress>,
+    mut autosave: ResMut<Autosave>,
+    mut error: ResMut<Option<ErrorMessage>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if let Some(ClickedButtonEvent(ClickedSelect(index))) = event_reader.iter().next() {
+        if *index != profiles.current_index() {
+            // switching should not lose what was done in the current profile
+            let save_file = SaveFile::new(&items, &progress);
+            match autosave.save_now(&profiles.save_path(), &save_file) {
+                // the newer save was left as is, nothing here to lose
+                Ok(()) | Err(SaveError::UnknownVersion(_)) => {}
+                Err(e) => {
+                    log::error!("cant save before switching profiles: {}", e);
+                    view.error = Some(format!("Could not save {}: {}", profiles.current(), e));
+                    return;
+                }
+            }
+            if let Err(e) = profiles.select(*index) {
+                log::error!("cant remember the selected profile: {}", e);
+            }
+            log::info!("switched to profile {}", profiles.current());
+            if let Some(message) = save::load(&profiles.save_path(), &mut items, &mut progress) {
+                *error = Some(message);
+            }
+        }
+        log::debug!("moving back to menu");
+        state.pop().expect("cant move back from profiles screen");
+    }
+}
+
+fn clicked_back(
+    mut event_reader: EventReader<ClickedButtonEvent<ClickedBack>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if event_reader.iter().next().is_some() {
+        log::debug!("moving back to menu");
+        state.pop().expect("cant move back from profiles screen");
+    }
+}
+
+pub struct ProfilesScreenPlugin;
+impl Plugin for ProfilesScreenPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::ProfilesScreen).with_system(setup.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::ProfilesScreen)
+                .with_system(cleanup_system::<ProfilesScreenMarker>.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::ProfilesScreen)
+                .with_system(clicked_back.system().after("button_click"))
+                .with_system(
+                    clicked_new
+                        .system()
+                        .after("button_click")
+                        .before("dispatch_profiles"),
+                )
+                .with_system(
+                    clicked_rename
+                        .system()
+                        .after("button_click")
+                        .before("dispatch_profiles"),
+                )
+                .with_system(
+                    clicked_delete
+                        .system()
+                        .after("button_click")
+                        .before("dispatch_profiles"),
+                )
+                .with_system(
+                    clicked_select
+                        .system()
+                        .after("button_click")
+                        .before("dispatch_profiles"),
+                )
+                .with_system(type_name.system().before("dispatch_profiles"))
+                .with_system(dispatch_profiles.system().label("dispatch_profiles"))
+                .with_system(change_camera_scale_from_resize.system()),
+        )
+        .init_resource::<ProfilesView>()
+        .init_resource::<Option<CurrentProfilesView>>();
+        register_my_button::<ClickedSelect>(app, GameState::ProfilesScreen);
+        register_my_button::<ClickedRename>(app, GameState::ProfilesScreen);
+        register_my_button::<ClickedDelete>(app, GameState::ProfilesScreen);
+        register_my_button::<ClickedNew>(app, GameState::ProfilesScreen);
+        register_my_button::<ClickedBack>(app, GameState::ProfilesScreen);
+    }
+}
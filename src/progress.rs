@@ -0,0 +1,57 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::objective::LevelResult;
+
+/// Best results of a level over all attempts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LevelProgress {
+    pub completed: bool,
+    /// Seconds, of completed runs only.
+    pub best_time: Option<f32>,
+    /// Fewest times the cameras started noticing the player in a completed run.
+    pub fewest_detections: Option<u32>,
+    pub most_treasures: u32,
+}
+
+/// Campaign progress, saved next to the items.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Progress {
+    #[serde(default)]
+    levels: BTreeMap<u32, LevelProgress>,
+    /// Playable without completing the required level, every level was in saves from before
+    /// progress was tracked.
+    #[serde(default)]
+    legacy_unlocked: BTreeSet<u32>,
+}
+
+impl Progress {
+    pub fn level(&self, level: u32) -> Option<&LevelProgress> {
+        self.levels.get(&level)
+    }
+
+    pub fn is_completed(&self, level: u32) -> bool {
+        self.level(level).map_or(false, |p| p.completed)
+    }
+
+    pub fn is_legacy_unlocked(&self, level: u32) -> bool {
+        self.legacy_unlocked.contains(&level)
+    }
+
+    pub fn record(&mut self, result: &LevelResult) {
+        let progress = self.levels.entry(result.level).or_default();
+        progress.completed = true;
+        progress.best_time = Some(
+            progress
+                .best_time
+                .map_or(result.time, |best| best.min(result.time)),
+        );
+        progress.fewest_detections = Some(
+            progress
+                .fewest_detections
+                .map_or(result.detections, |best| best.min(result.detections)),
+        );
+        progress.most_treasures = progress.most_treasures.max(result.treasures);
+    }
+}
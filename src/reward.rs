@@ -1,15 +1,16 @@
 use std::f32::consts::PI;
 
 use bevy::{log, math::Mat2, prelude::*};
-use itertools::Itertools;
 
 use crate::{
     button::{register_my_button, ClickedButtonEvent, MyButton, MyButtonBundle},
     cleanup::cleanup_system,
-    items::{generate, Item, PlayerItems},
-    main_menu_ui::light_text_color,
+    items::{generate, Item, PlayerItems, Rarity},
+    loot::{LootRng, LootTables},
+    main_menu_ui::{light_text_color, SelectedLevel},
     perlin::{PerlinBundle, PerlinPipelineHandle},
     player::Player,
+    uniques::Uniques,
     GameState, RobotoFont,
 };
 
@@ -45,8 +46,13 @@ fn generate_rewards(
     mut meshes: ResMut<Assets<Mesh>>,
     font: Res<RobotoFont>,
     player: Query<&Transform, With<Player>>,
+    level: Res<SelectedLevel>,
+    tables: Res<LootTables>,
+    uniques: Res<Uniques>,
+    mut loot: ResMut<LootRng>,
 ) {
-    let items = generate();
+    log::debug!("rolling rewards, run seed {}", loot.seed());
+    let items = generate(loot.rng(), tables.for_level(level.0), &uniques);
     let tr = player.single().expect("single player").translation;
     commands.insert_resource(RewardItems(items.clone()));
 
@@ -71,10 +77,11 @@ fn generate_rewards(
             Vec3::new(0.05, 0.05, 0.01),
         ))
         .with_children(|cmds| {
+            let middle = (items.len() as f32 - 1.) / 2.;
             for (i, item) in items.into_iter().enumerate() {
                 let sprite = Sprite::new(Vec2::new(CARD_WIDTH, CARD_HEIGHT) * 2.);
                 let texture = common.0.clone();
-                let x = (i as f32 - 1.) * 500.;
+                let x = (i as f32 - middle) * 500.;
                 cmds.spawn_bundle(MeshBundle {
                     mesh: meshes.add(build_card_mesh()),
                     transform: Transform::from_xyz(x, 0., 0.1),
@@ -84,7 +91,7 @@ fn generate_rewards(
                     &pp_handle,
                     CARD_SHADER_RESOLUTION,
                     CARD_SHADER_OCTAVE,
-                    get_card_shader_color(item.mods.len()),
+                    get_card_shader_color(item.rarity),
                 ))
                 .with_children(|cmds| {
                     cmds.spawn_bundle(SpriteBundle {
@@ -116,12 +123,7 @@ fn generate_rewards(
                             ..Default::default()
                         });
 
-                        let text = item
-                            .mods
-                            .iter()
-                            .map(|item_mod| item_mod.to_string())
-                            .collect_vec()
-                            .join("\n");
+                        let text = item.description();
                         let item_mods = Text::with_section(
                             text,
                             TextStyle {
@@ -183,7 +185,7 @@ fn clicked_select(
         log::debug!("selecting a treasure");
         let item = rewards.0[*index].clone();
         items.slot_items_mut(item.slot).available.push(item);
-        log::debug!("moving back to menu");
+        log::debug!("moving back to the level");
         state.pop().expect("cant move back from reward screen");
     }
 }
@@ -267,12 +269,12 @@ fn build_card_mesh() -> Mesh {
     mesh
 }
 
-fn get_card_shader_color(mods_len: usize) -> Vec3 {
-    match mods_len {
-        1 => Vec3::new(0.1, 1., 0.1),
-        2 => Vec3::new(0.1, 0.1, 1.),
-        4 => Vec3::new(0.7, 0.7, 0.1),
-        _ => unreachable!(),
+fn get_card_shader_color(rarity: Rarity) -> Vec3 {
+    match rarity {
+        Rarity::Common => Vec3::new(0.1, 1., 0.1),
+        Rarity::Magic => Vec3::new(0.1, 0.1, 1.),
+        Rarity::Rare => Vec3::new(0.7, 0.7, 0.1),
+        Rarity::Unique => Vec3::new(0.9, 0.35, 0.05),
     }
 }
 
@@ -0,0 +1,315 @@
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{log, prelude::*};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    error_screen::ErrorMessage,
+    items::{PlayerItems, Rarity},
+    profiles::Profiles,
+    progress::Progress,
+};
+
+/// Bump on every change of `SaveFile` layout and add a step to `migrate`.
+pub const SAVE_VERSION: u64 = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub version: u64,
+    pub items: PlayerItems,
+    pub progress: Progress,
+}
+
+impl SaveFile {
+    pub fn new(items: &PlayerItems, progress: &Progress) -> Self {
+        SaveFile {
+            version: SAVE_VERSION,
+            items: items.clone(),
+            progress: progress.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// Written by a newer version of the game.
+    UnknownVersion(u64),
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "cant access the save: {}", e),
+            SaveError::Json(e) => write!(f, "the save is damaged: {}", e),
+            SaveError::UnknownVersion(v) => {
+                write!(f, "the save is from a newer version of the game ({})", v)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Json(e)
+    }
+}
+
+/// `Ok(None)` if there is no save yet.
+pub fn read(path: &Path) -> Result<Option<SaveFile>, SaveError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(x) => x,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let value: Value = serde_json::from_str(&contents)?;
+    let value = migrate(value)?;
+    Ok(Some(serde_json::from_value(value)?))
+}
+
+/// Writes to a temporary file first and renames it over the save, the previous save is kept
+/// as a backup. A crash in the middle leaves either the old or the new save, never half of one.
+/// A save from a newer version of the game is never written over.
+pub fn write(path: &Path, save: &SaveFile) -> Result<(), SaveError> {
+    // whatever the newer version added would be lost
+    if let Some(version) = saved_version(path).filter(|v| *v > SAVE_VERSION) {
+        return Err(SaveError::UnknownVersion(version));
+    }
+    let json = serde_json::to_string_pretty(save)?;
+    let tmp_path = with_suffix(path, "tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+    }
+    if path.exists() {
+        fs::copy(path, backup_path(path))?;
+    }
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, "bak")
+}
+
+/// Moves a broken save out of the way, so it is neither loaded nor overwritten.
+pub fn quarantine(path: &Path) -> io::Result<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let target = with_suffix(path, &format!("corrupt-{}", secs));
+    fs::rename(path, &target)?;
+    Ok(target)
+}
+
+// save.json -> save.json.tmp
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// `None` if there is no save or it cant be parsed.
+fn saved_version(path: &Path) -> Option<u64> {
+    let contents = fs::read_to_string(path).ok()?;
+    let value: Value = serde_json::from_str(&contents).ok()?;
+    Some(version_of(&value))
+}
+
+fn version_of(value: &Value) -> u64 {
+    // the first saves were a bare `PlayerItems`
+    value.get("version").and_then(Value::as_u64).unwrap_or(0)
+}
+
+/// Items were saved without a rarity, it used to be read from the number of mods.
+fn add_rarity(items: &mut Value) {
+    for slot in ["head", "cloak", "lockpick", "boots"] {
+        let available = items
+            .get_mut(slot)
+            .and_then(|slot| slot.get_mut("available"))
+            .and_then(Value::as_array_mut);
+        for item in available.into_iter().flatten() {
+            let mods = item
+                .get("mods")
+                .and_then(Value::as_array)
+                .map_or(0, Vec::len);
+            if let Some(item) = item.as_object_mut() {
+                item.insert("rarity".to_string(), json!(Rarity::from_mod_count(mods)));
+            }
+        }
+    }
+}
+
+/// Upgrades a save of any known version to the `SAVE_VERSION` layout, one version at a time.
+fn migrate(mut value: Value) -> Result<Value, SaveError> {
+    loop {
+        let version = version_of(&value);
+        value = match version {
+            SAVE_VERSION => return Ok(value),
+            0 => json!({ "version": 1, "items": value }),
+            // every level was playable before progress was tracked, these are the ones there were
+            1 => json!({
+                "version": 2,
+                "items": value["items"],
+                "progress": { "levels": {}, "legacy_unlocked": [1, 2] }
+            }),
+            2 => {
+                let mut items = value["items"].clone();
+                add_rarity(&mut items);
+                json!({ "version": 3, "items": items, "progress": value["progress"] })
+            }
+            // crafting came with shards, nothing was salvaged before it
+            3 => {
+                let mut items = value["items"].clone();
+                if let Some(items) = items.as_object_mut() {
+                    items.insert("shards".to_string(), json!(0));
+                }
+                json!({ "version": 4, "items": items, "progress": value["progress"] })
+            }
+            _ => return Err(SaveError::UnknownVersion(version)),
+        };
+    }
+}
+
+/// Loads the save into `items` and `progress`, falling back to the backup and then to a fresh start.
+/// A save from a newer version is left where it is and the game starts fresh without writing over it.
+/// Returns what went wrong for the menu to show.
+pub fn load(path: &Path, items: &mut PlayerItems, progress: &mut Progress) -> Option<ErrorMessage> {
+    *items = PlayerItems::default();
+    *progress = Progress::default();
+    let problem = match read(path) {
+        Ok(Some(save)) => {
+            *items = save.items;
+            *progress = save.progress;
+            return None;
+        }
+        Ok(None) => return None,
+        Err(e) => e,
+    };
+    log::error!("cant load {}: {}", path.display(), problem);
+    if let SaveError::UnknownVersion(_) = problem {
+        return Some(ErrorMessage {
+            title: "Could not load the save".to_string(),
+            details: format!(
+                "{}\nIt was left as is and will not be saved over, update the game to play this profile.",
+                problem
+            ),
+        });
+    }
+    let mut details = format!("{}", problem);
+    match quarantine(path) {
+        Ok(moved) => details += &format!("\nIt was moved to {}", moved.display()),
+        Err(e) => log::error!("cant quarantine {}: {}", path.display(), e),
+    }
+    let backup = backup_path(path);
+    match read(&backup) {
+        Ok(Some(save)) => {
+            *items = save.items;
+            *progress = save.progress;
+            details += "\nThe backup from the previous save was loaded instead.";
+        }
+        Ok(None) => details += "\nStarting from scratch.",
+        Err(e) => {
+            log::error!("cant load {}: {}", backup.display(), e);
+            details += "\nThe backup is damaged too, starting from scratch.";
+        }
+    }
+    Some(ErrorMessage {
+        title: "Could not load the save".to_string(),
+        details,
+    })
+}
+
+fn load_save(
+    profiles: Res<Profiles>,
+    mut items: ResMut<PlayerItems>,
+    mut progress: ResMut<Progress>,
+    mut error: ResMut<Option<ErrorMessage>>,
+) {
+    if let Some(message) = load(&profiles.save_path(), &mut items, &mut progress) {
+        *error = Some(message);
+    }
+}
+
+pub struct SavePlugin;
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Profiles>()
+            .init_resource::<Progress>()
+            .add_startup_system(load_save.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::levels::{Difficulty, LevelInfo};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rustyjam1-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn levels_stay_unlocked_after_progress_is_added() {
+        let old = json!({ "version": 1, "items": PlayerItems::default() });
+        let save: SaveFile = serde_json::from_value(migrate(old).unwrap()).unwrap();
+        let level2 = LevelInfo {
+            id: 2,
+            name: "Level 2".to_string(),
+            map: "level2.tmx".to_string(),
+            description: String::new(),
+            difficulty: Difficulty::Normal,
+            requires: Some(1),
+        };
+        assert!(level2.is_unlocked(&save.progress));
+        // nothing was completed back then
+        assert!(!save.progress.is_completed(1));
+        assert!(save.progress.level(1).is_none());
+    }
+
+    #[test]
+    fn newer_save_is_left_alone() {
+        let dir = test_dir("newer-save");
+        let path = dir.join("save.json");
+        let newer = json!({ "version": SAVE_VERSION + 1, "items": {}, "progress": {} }).to_string();
+        fs::write(&path, &newer).unwrap();
+        fs::write(backup_path(&path), "{}").unwrap();
+
+        let mut items = PlayerItems::default();
+        let mut progress = Progress::default();
+        assert!(load(&path, &mut items, &mut progress).is_some());
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+        // neither quarantined nor replaced by the backup
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        let save = SaveFile::new(&items, &progress);
+        assert!(matches!(
+            write(&path, &save),
+            Err(SaveError::UnknownVersion(v)) if v == SAVE_VERSION + 1
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
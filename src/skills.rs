@@ -1,8 +1,11 @@
 use std::time::Duration;
 
-use bevy::prelude::*;
+use bevy::{ecs::component::Component, prelude::*};
 
-use crate::{cleanup::cleanup_system, player::SpellKind, GameState};
+use crate::{
+    camera_enemy::Suspicion, cleanup::cleanup_system, items::PlayerStatsMods, mana::Mana,
+    player::SpellKind, GameState,
+};
 
 const BASE_WIDTH: f32 = 352. / 2560. * 100. * 0.75;
 const BASE_HEIGHT: f32 = 227. / 1440. * 100. * 0.75;
@@ -13,6 +16,9 @@ const BASE_HEIGHT: f32 = 227. / 1440. * 100. * 0.75;
 struct SkillsUiHandles {
     layout: Handle<ColorMaterial>,
     none_material: Handle<ColorMaterial>,
+    bar_background: Handle<ColorMaterial>,
+    suspicion_fill: Handle<ColorMaterial>,
+    mana_fill: Handle<ColorMaterial>,
 }
 
 fn get_handle(world: &mut World, path: &str) -> Handle<ColorMaterial> {
@@ -33,9 +39,15 @@ impl FromWorld for SkillsUiHandles {
             .get_resource_mut::<Assets<ColorMaterial>>()
             .expect("no materials");
         let none_material = color_materials.add(Color::NONE.into());
+        let bar_background = color_materials.add(Color::rgb_u8(34, 17, 17).into());
+        let suspicion_fill = color_materials.add(Color::rgb(0.9, 0.1, 0.).into());
+        let mana_fill = color_materials.add(Color::rgb(0.15, 0.35, 0.9).into());
         SkillsUiHandles {
             layout,
             none_material,
+            bar_background,
+            suspicion_fill,
+            mana_fill,
         }
     }
 }
@@ -69,7 +81,13 @@ impl SkillsState {
             if let Some(time_to_cd) = &mut state.time_to_cd {
                 time_to_cd.tick(delta);
                 if time_to_cd.finished() {
-                    state.time_to_cd = None;
+                    state.charges += 1;
+                    if state.charges < state.max_charges {
+                        // charges come back one at a time
+                        time_to_cd.reset();
+                    } else {
+                        state.time_to_cd = None;
+                    }
                 }
             }
         }
@@ -82,11 +100,48 @@ impl SkillsState {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct SkillState {
+    pub charges: u32,
+    pub max_charges: u32,
+    /// Recharge of the next charge.
     pub time_to_cd: Option<Timer>,
 }
 
+impl Default for SkillState {
+    fn default() -> Self {
+        SkillState::new(1)
+    }
+}
+
+impl SkillState {
+    pub fn new(max_charges: u32) -> Self {
+        SkillState {
+            charges: max_charges,
+            max_charges,
+            time_to_cd: None,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.charges > 0
+    }
+
+    /// Spends a charge and starts recharging it, unless another charge is recharging already.
+    pub fn use_charge(&mut self, cooldown: f32) {
+        self.charges = self.charges.saturating_sub(1);
+        if self.time_to_cd.is_none() {
+            self.time_to_cd = Some(Timer::from_seconds(cooldown, false));
+        }
+    }
+}
+
+fn reset_states(mut skills_state: ResMut<SkillsState>, stats: Res<PlayerStatsMods>) {
+    for kind in [SpellKind::Dash, SpellKind::Smoke, SpellKind::Emp] {
+        *skills_state.get_state_mut(kind) = SkillState::new(kind.params(&stats).charges);
+    }
+}
+
 struct SkillsUiMarker;
 
 trait UiSkill {
@@ -110,6 +165,54 @@ impl UiSkill for SpellKind {
 
 struct CooldownMarker;
 struct IconMarker;
+struct SuspicionBarMarker;
+struct ManaBarMarker;
+
+const BAR_HEIGHT: f32 = 12.;
+
+// bottom is in percents of the window height, same as the skills panel
+fn spawn_bar(
+    commands: &mut Commands,
+    textures: &SkillsUiHandles,
+    bottom: f32,
+    fill: Handle<ColorMaterial>,
+    fill_percent: f32,
+    marker: impl Component,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(30.),
+                    bottom: Val::Percent(bottom),
+                    ..Default::default()
+                },
+                size: Size {
+                    width: Val::Percent(BASE_WIDTH),
+                    height: Val::Px(BAR_HEIGHT),
+                },
+                ..Default::default()
+            },
+            material: textures.bar_background.clone(),
+            ..Default::default()
+        })
+        .insert(SkillsUiMarker)
+        .with_children(|ec| {
+            ec.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size {
+                        width: Val::Percent(fill_percent),
+                        height: Val::Percent(100.),
+                    },
+                    ..Default::default()
+                },
+                material: fill,
+                ..Default::default()
+            })
+            .insert(marker);
+        });
+}
 
 fn setup(
     mut commands: Commands,
@@ -121,6 +224,23 @@ fn setup(
         .commands();
     let material = textures.none_material.clone();
     let font_handle = asset_server.load("FiraSans-Bold.ttf");
+    // mana right above the skills, suspicion meter above it
+    spawn_bar(
+        ui_cmds,
+        &textures,
+        BASE_HEIGHT + 5.,
+        textures.mana_fill.clone(),
+        100.,
+        ManaBarMarker,
+    );
+    spawn_bar(
+        ui_cmds,
+        &textures,
+        BASE_HEIGHT + 8.,
+        textures.suspicion_fill.clone(),
+        0.,
+        SuspicionBarMarker,
+    );
     ui_cmds
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -213,7 +333,7 @@ fn setup(
                             ..Default::default()
                         })
                         .with_children(|ec| {
-                            let text = Text::with_section(
+                            let mut text = Text::with_section(
                                 s.icon_name(),
                                 TextStyle {
                                     font: font_handle.clone(),
@@ -225,6 +345,15 @@ fn setup(
                                     horizontal: HorizontalAlign::Center,
                                 },
                             );
+                            // charges count, empty for single charge skills
+                            text.sections.push(TextSection {
+                                value: String::new(),
+                                style: TextStyle {
+                                    font: font_handle.clone(),
+                                    font_size: 20.0,
+                                    color: Color::WHITE,
+                                },
+                            });
                             ec.spawn_bundle(TextBundle {
                                 text,
                                 ..Default::default()
@@ -266,11 +395,37 @@ fn update_texts(
         });
         icons.for_each_mut(|(mut text, kind)| {
             let state = skills_state.get_state(*kind);
-            if state.time_to_cd.is_some() {
-                text.sections[0].style.color = Color::GRAY;
+            let color = if state.is_ready() {
+                Color::WHITE
             } else {
-                text.sections[0].style.color = Color::WHITE;
-            }
+                Color::GRAY
+            };
+            text.sections[0].style.color = color;
+            text.sections[1].style.color = color;
+            text.sections[1].value = if state.max_charges > 1 {
+                format!(" {}", state.charges)
+            } else {
+                String::new()
+            };
+        });
+    }
+}
+
+fn update_suspicion_bar(
+    suspicion: Res<Suspicion>,
+    bars: Query<&mut Style, With<SuspicionBarMarker>>,
+) {
+    if suspicion.is_changed() {
+        bars.for_each_mut(|mut style| {
+            style.size.width = Val::Percent(suspicion.0 * 100.);
+        });
+    }
+}
+
+fn update_mana_bar(mana: Res<Mana>, bars: Query<&mut Style, With<ManaBarMarker>>) {
+    if mana.is_changed() {
+        bars.for_each_mut(|mut style| {
+            style.size.width = Val::Percent(mana.fraction() * 100.);
         });
     }
 }
@@ -280,11 +435,17 @@ impl Plugin for SkillsUiPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<SkillsUiHandles>()
             .init_resource::<SkillsState>()
-            .add_system_set(SystemSet::on_enter(GameState::Level).with_system(setup.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Level)
+                    .with_system(setup.system())
+                    .with_system(reset_states.system()),
+            )
             .add_system_set(
                 SystemSet::on_update(GameState::Level)
                     .with_system(tick_states.system().label("skills_ui_tick"))
-                    .with_system(update_texts.system().after("skills_ui_tick")),
+                    .with_system(update_texts.system().after("skills_ui_tick"))
+                    .with_system(update_suspicion_bar.system())
+                    .with_system(update_mana_bar.system()),
             )
             .add_system_set(
                 SystemSet::on_exit(GameState::Level)
@@ -292,3 +453,40 @@ impl Plugin for SkillsUiPlugin {
             );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dash_charges_recharge_one_at_a_time() {
+        let stats = PlayerStatsMods {
+            dash_charges: 1,
+            ..Default::default()
+        };
+        let params = SpellKind::Dash.params(&stats);
+        let mut skills_state = SkillsState::default();
+        *skills_state.get_state_mut(SpellKind::Dash) = SkillState::new(params.charges);
+        let charges = |skills_state: &SkillsState| skills_state.get_state(SpellKind::Dash).charges;
+        assert_eq!(charges(&skills_state), 2);
+
+        skills_state
+            .get_state_mut(SpellKind::Dash)
+            .use_charge(params.cooldown);
+        assert_eq!(charges(&skills_state), 1);
+        assert!(skills_state.get_state(SpellKind::Dash).is_ready());
+        skills_state
+            .get_state_mut(SpellKind::Dash)
+            .use_charge(params.cooldown);
+        assert_eq!(charges(&skills_state), 0);
+        assert!(!skills_state.get_state(SpellKind::Dash).is_ready());
+
+        let cooldown = Duration::from_secs_f32(params.cooldown);
+        skills_state.tick_states(cooldown);
+        assert_eq!(charges(&skills_state), 1);
+        assert!(skills_state.needs_to_tick());
+        skills_state.tick_states(cooldown);
+        assert_eq!(charges(&skills_state), 2);
+        assert!(!skills_state.needs_to_tick());
+    }
+}
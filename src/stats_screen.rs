@@ -80,11 +80,22 @@ fn setup(
                     ..Default::default()
                 });
 
-                let text = items
+                // totals already include the set bonuses, listed again to show where they come from
+                let mut text = items
                     .all_equipped_mods()
                     .map(|a_mod| a_mod.to_string())
+                    .chain(
+                        items
+                            .all_equipped_items()
+                            .flat_map(|item| item.effects.iter().map(|effect| effect.to_string())),
+                    )
                     .collect_vec()
                     .join("\n");
+                let sets = items.set_description();
+                if !sets.is_empty() {
+                    text += "\n\nSet bonuses\n";
+                    text += &sets;
+                }
 
                 let item_mods = Text::with_section(
                     text,
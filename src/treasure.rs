@@ -10,6 +10,7 @@ use bevy::{
 };
 
 use crate::{
+    objective::LevelObjective,
     perlin::TimeComponent,
     player::{LevelMarker, Player},
     GameState,
@@ -61,15 +62,19 @@ fn spawn_treasure(
 }
 
 fn treasure_collide(
+    mut commands: Commands,
     player: Query<&Transform, With<Player>>,
-    treasures: Query<&Transform, With<TreasureSpawn>>,
+    treasures: Query<(Entity, &Transform), With<TreasureSpawn>>,
     mut state: ResMut<State<GameState>>,
+    mut objective: ResMut<LevelObjective>,
 ) {
     let tr = player.single().expect("single player").translation;
-    for tr_tr in treasures.iter() {
+    for (entity, tr_tr) in treasures.iter() {
         if collide_aabb::collide(tr, Vec2::splat(100.), tr_tr.translation, Vec2::splat(100.))
             .is_some()
         {
+            commands.entity(entity).despawn_recursive();
+            objective.treasures_collected += 1;
             state
                 .push(GameState::ChoosingTreasure)
                 .expect("cant move to treasure choosing");
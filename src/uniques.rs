@@ -0,0 +1,105 @@
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use bevy::{asset::FileAssetIo, log, prelude::*};
+use rand::{prelude::SliceRandom, Rng};
+use serde::Deserialize;
+
+use crate::{
+    effects::Effect,
+    error_screen::ErrorMessage,
+    items::{Item, Mod, Rarity, Slot},
+};
+
+/// Relative to the assets folder.
+const UNIQUES: &str = "uniques.json";
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// A hand made item, it always drops with the same mods.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UniqueInfo {
+    pub name: String,
+    pub slot: Slot,
+    #[serde(default)]
+    pub mods: Vec<Mod>,
+    pub effects: Vec<Effect>,
+    /// Against the other uniques that can drop.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+impl UniqueInfo {
+    pub fn to_item(&self) -> Item {
+        Item {
+            name: self.name.clone(),
+            slot: self.slot,
+            rarity: Rarity::Unique,
+            mods: self.mods.clone(),
+            effects: self.effects.clone(),
+            set: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Uniques(pub Vec<UniqueInfo>);
+
+impl Uniques {
+    /// `None` if no unique fits any of the slots.
+    pub fn choose(&self, rng: &mut impl Rng, slots: &[Slot]) -> Option<Item> {
+        let fitting: Vec<_> = self
+            .0
+            .iter()
+            .filter(|unique| slots.contains(&unique.slot))
+            .collect();
+        fitting
+            .choose_weighted(rng, |unique| unique.weight)
+            .ok()
+            .map(|unique| unique.to_item())
+    }
+}
+
+fn uniques_path() -> PathBuf {
+    FileAssetIo::get_root_path().join("assets").join(UNIQUES)
+}
+
+fn read_uniques() -> Result<Vec<UniqueInfo>, String> {
+    let path = uniques_path();
+    let file = File::open(&path).map_err(|e| format!("cant open {}: {}", path.display(), e))?;
+    let uniques: Vec<UniqueInfo> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("{} is malformed: {}", path.display(), e))?;
+    if let Some(unique) = uniques.iter().find(|unique| unique.effects.is_empty()) {
+        return Err(format!(
+            "unique {} in {} has no effects",
+            unique.name, UNIQUES
+        ));
+    }
+    Ok(uniques)
+}
+
+fn load_uniques(mut uniques: ResMut<Uniques>, mut error: ResMut<Option<ErrorMessage>>) {
+    match read_uniques() {
+        Ok(loaded) => {
+            log::info!("{} uniques", loaded.len());
+            uniques.0 = loaded;
+        }
+        Err(e) => {
+            // unique drops turn into rares without them
+            log::error!("cant load uniques: {}", e);
+            *error = Some(ErrorMessage {
+                title: "Could not load the unique items".to_string(),
+                details: e,
+            });
+        }
+    }
+}
+
+pub struct UniquesPlugin;
+impl Plugin for UniquesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Uniques>()
+            .add_startup_system(load_uniques.system());
+    }
+}